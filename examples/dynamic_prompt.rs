@@ -0,0 +1,45 @@
+extern crate repl_rs;
+
+use repl_rs::{Command, Parameter, Result, Value};
+use repl_rs::{Paint, Prompt, Repl};
+use std::collections::HashMap;
+
+// Example using Repl with a prompt which shows the current directory
+
+#[derive(Default)]
+struct Context {
+    path: Vec<String>,
+}
+
+// Change into a directory
+fn cd(args: HashMap<String, Value>, context: &mut Context) -> Result<Option<String>> {
+    match args["dir"].to_string().as_str() {
+        ".." => {
+            context.path.pop();
+        }
+        dir => context.path.push(dir.to_string()),
+    }
+
+    Ok(None)
+}
+
+fn main() -> Result<()> {
+    let mut repl = Repl::new(Context::default())
+        .with_name("MyApp")
+        .with_version("v0.1.0")
+        .with_description("My very cool app")
+        .with_prompt_fn(|context: &Context| {
+            Prompt::new()
+                .with_separator(":")
+                .with_suffix("> ")
+                .segment(Paint::green("MyApp").bold())
+                .segment(Paint::blue(format!("/{}", context.path.join("/"))))
+                .to_string()
+        })
+        .add_command(
+            Command::new("cd", cd)
+                .with_parameter(Parameter::new("dir").set_required(true)?)?
+                .with_help("Change directory"),
+        );
+    repl.run()
+}
@@ -5,7 +5,7 @@ use repl_rs::{initialize_repl, Convert, Repl};
 use repl_rs::{Command, Parameter, Result, Value};
 use std::collections::{HashMap, VecDeque};
 
-/// Example using initialize_repl
+// Example using initialize_repl
 
 #[derive(Default)]
 struct Context {
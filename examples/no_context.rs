@@ -4,7 +4,7 @@ use repl_rs::{Command, Parameter, Result, Value};
 use repl_rs::{Convert, Repl};
 use std::collections::HashMap;
 
-/// Example using Repl without Context (or, more precisely, a Context of ())
+// Example using Repl without Context (or, more precisely, a Context of ())
 
 // Add two numbers. Have to make this generic to be able to pass a Context of type ()
 fn add<T>(args: HashMap<String, Value>, _context: &mut T) -> Result<Option<String>> {
@@ -4,7 +4,7 @@ use repl_rs::{Command, Parameter, Result, Value};
 use repl_rs::{Convert, Repl};
 use std::collections::{HashMap, VecDeque};
 
-/// Example using Repl with Context
+// Example using Repl with Context
 
 #[derive(Default)]
 struct Context {
@@ -35,14 +35,24 @@ fn main() -> Result<()> {
         .with_version("v0.1.0")
         .with_description("My very cool app")
         .use_completion(true)
+        .use_hints(true)
+        .use_highlighting(true)
         .add_command(
             Command::new("append", append)
-                .with_parameter(Parameter::new("name").set_required(true)?)?
+                .with_parameter(
+                    Parameter::new("name")
+                        .set_required(true)?
+                        .with_help("Name to append"),
+                )?
                 .with_help("Append name to end of list"),
         )
         .add_command(
             Command::new("prepend", prepend)
-                .with_parameter(Parameter::new("name").set_required(true)?)?
+                .with_parameter(
+                    Parameter::new("name")
+                        .set_required(true)?
+                        .with_help("Name to prepend"),
+                )?
                 .with_help("Prepend name to front of list"),
         );
     repl.run()
@@ -0,0 +1,407 @@
+//! Derive macro for [repl-rs](https://docs.rs/repl-rs) commands. Use it through repl-rs, with its
+//! `derive` feature turned on, rather than depending on this crate directly.
+//!
+//! `#[derive(ReplCommand)]` on a struct of arguments generates the command's parameters from
+//! the fields, and its help from the doc comments, and converts the arguments into the struct
+//! before your handler is called:
+//! ```
+//! use repl_rs::{Command, Repl, ReplCommand, Result};
+//! use std::path::PathBuf;
+//!
+//! /// Copy files into a directory
+//! #[derive(ReplCommand)]
+//! #[repl(name = "cp")]
+//! struct Copy {
+//!     /// Directory to copy into
+//!     target: PathBuf,
+//!
+//!     /// Files to copy
+//!     files: Vec<PathBuf>,
+//!
+//!     /// How many times to retry
+//!     #[repl(short = 'r', long, default = "3")]
+//!     retries: u32,
+//!
+//!     /// Overwrite existing files
+//!     force: bool,
+//! }
+//!
+//! fn copy(args: Copy, _context: &mut ()) -> Result<Option<String>> {
+//!     Ok(Some(format!(
+//!         "Copying {} files to {} with {} retries",
+//!         args.files.len(),
+//!         args.target.display(),
+//!         args.retries
+//!     )))
+//! }
+//!
+//! fn main() -> Result<()> {
+//!     let mut repl = Repl::new(()).add_command(Command::typed(copy)?);
+//!     repl.run()
+//! }
+//! ```
+//!
+//! How each field becomes a parameter depends on its type:
+//! - `bool` fields are flags, given as `--<name>`
+//! - `Option<T>` fields are optional
+//! - `Vec<T>` fields take any number of values: positionally if they're the last positional
+//!   parameter, or by giving the option more than once if they're named options
+//! - other fields are required, unless they have a default
+//!
+//! Float, `bool`, `PathBuf` and `String` fields get the matching
+//! [ParameterType](https://docs.rs/repl-rs/latest/repl_rs/enum.ParameterType.html). Integer
+//! fields are checked against their own type, so a negative value for a `usize` is rejected
+//! as an invalid argument, and fields of any other type implementing `FromStr`, like
+//! `IpAddr`, are parsed with it. Field names are used as parameter names, and as long option
+//! names with underscores changed to dashes.
+//!
+//! These attributes can be given on the struct:
+//! - `#[repl(name = "...")]`: the command's name, which otherwise is the struct's name in
+//!   kebab-case
+//!
+//! and on fields:
+//! - `#[repl(long)]` or `#[repl(long = "...")]`: make it a named option
+//! - `#[repl(short = 'x')]`: make it a named option with a short name
+//! - `#[repl(default = "...")]`: the value used when it's not given
+//! - `#[repl(choices("a", "b"))]`: restrict it to the given values
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::punctuated::Punctuated;
+use syn::{
+    parse_macro_input, Attribute, Data, DeriveInput, Error, Expr, Fields, GenericArgument, Lit,
+    LitChar, LitStr, PathArguments, Token, Type,
+};
+
+/// Derive `repl_rs::ReplCommand` for a struct of command arguments. See the
+/// [crate documentation](index.html) for the attributes it takes.
+#[proc_macro_derive(ReplCommand, attributes(repl))]
+pub fn derive_repl_command(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+// How a field's values are given
+enum Shape {
+    // A single value
+    Single,
+
+    // `Option<T>`, which can be left out
+    Optional,
+
+    // `Vec<T>`, which takes any number of values
+    List,
+
+    // `bool`, which is a flag
+    Flag,
+}
+
+// What's been given in a field's `#[repl(...)]` attributes
+#[derive(Default)]
+struct FieldOptions {
+    long: Option<Option<String>>,
+    short: Option<char>,
+    default: Option<String>,
+    choices: Vec<String>,
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let ident = &input.ident;
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(Error::new_spanned(
+                    ident,
+                    "ReplCommand can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(Error::new_spanned(
+                ident,
+                "ReplCommand can only be derived for structs",
+            ))
+        }
+    };
+
+    let mut name = kebab_case(&ident.to_string());
+    for attr in repl_attributes(&input.attrs) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("name") {
+                name = meta.value()?.parse::<LitStr>()?.value();
+                Ok(())
+            } else {
+                Err(meta.error("expected `name`"))
+            }
+        })?;
+    }
+    let help = match doc_comment(&input.attrs) {
+        Some(help) => quote!(Some(#help.to_string())),
+        None => quote!(None),
+    };
+
+    let mut parameters = vec![];
+    let mut extractions = vec![];
+    for field in fields {
+        let field_ident = field.ident.as_ref().unwrap();
+        let parameter_name = field_ident.to_string();
+        let options = field_options(&field.attrs)?;
+        let (shape, value_type) = shape(&field.ty);
+
+        let mut parameter = quote!(::repl_rs::Parameter::new(#parameter_name));
+        let named = options.long.is_some() || options.short.is_some();
+        if let Some(long) = &options.long {
+            let long = long
+                .clone()
+                .unwrap_or_else(|| parameter_name.replace('_', "-"));
+            parameter = quote!(#parameter.set_long(#long));
+        }
+        if let Some(short) = options.short {
+            parameter = quote!(#parameter.set_short(#short));
+        }
+        let kind = parameter_type(value_type);
+        parameter = quote!(#parameter.set_type(#kind)?);
+        match shape {
+            Shape::Flag => parameter = quote!(#parameter.set_flag(true)?),
+            Shape::List if named => parameter = quote!(#parameter.set_multiple(true)),
+            Shape::List => parameter = quote!(#parameter.set_variadic(0, None)?),
+            Shape::Single if options.default.is_none() => {
+                parameter = quote!(#parameter.set_required(true)?)
+            }
+            _ => (),
+        }
+        if let Some(default) = &options.default {
+            parameter = quote!(#parameter.set_default(#default)?);
+        }
+        if !options.choices.is_empty() {
+            let choices = &options.choices;
+            parameter = quote!(#parameter.set_choices(&[#(#choices),*])?);
+        }
+        if let Some(help) = doc_comment(&field.attrs) {
+            parameter = quote!(#parameter.with_help(#help));
+        }
+        parameters.push(parameter);
+
+        let value = quote!(args.get(#parameter_name));
+        // Types repl-rs can't convert to are parsed from the string with `FromStr`
+        let (convert, convert_list) = match is_convertible(value_type) {
+            true => (
+                quote!(::repl_rs::Convert::<#value_type>::convert),
+                quote!(::repl_rs::Convert::<Vec<#value_type>>::convert),
+            ),
+            false => {
+                let parse = quote! {
+                    |value: String| value.parse::<#value_type>().map_err(|_| {
+                        ::repl_rs::Error::InvalidArgument(
+                            #parameter_name.into(),
+                            #kind.to_string(),
+                            value,
+                        )
+                    })
+                };
+                (
+                    quote! {
+                        |value: &::repl_rs::Value| (#parse)(value.to_string())
+                    },
+                    quote! {
+                        |value: &::repl_rs::Value| {
+                            ::repl_rs::Convert::<Vec<String>>::convert(value)?
+                                .into_iter()
+                                .map(#parse)
+                                .collect::<::repl_rs::Result<Vec<#value_type>>>()
+                        }
+                    },
+                )
+            }
+        };
+        let extraction = match shape {
+            Shape::Optional => quote! {
+                #value
+                    .map(#convert)
+                    .transpose()?
+            },
+            Shape::List => quote! {
+                #value
+                    .map(#convert_list)
+                    .transpose()?
+                    .unwrap_or_default()
+            },
+            Shape::Single | Shape::Flag => quote! {
+                (#convert)(#value.ok_or_else(|| {
+                    ::repl_rs::Error::MissingRequiredArgument(#name.into(), #parameter_name.into())
+                })?)?
+            },
+        };
+        extractions.push(quote!(#field_ident: #extraction));
+    }
+
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::repl_rs::ReplCommand for #ident #type_generics #where_clause {
+            fn name() -> String {
+                #name.to_string()
+            }
+
+            fn help() -> Option<String> {
+                #help
+            }
+
+            fn parameters() -> ::repl_rs::Result<Vec<::repl_rs::Parameter>> {
+                Ok(vec![#(#parameters),*])
+            }
+
+            fn from_args(
+                args: &::std::collections::HashMap<String, ::repl_rs::Value>,
+            ) -> ::repl_rs::Result<Self> {
+                Ok(Self {
+                    #(#extractions),*
+                })
+            }
+        }
+    })
+}
+
+fn repl_attributes(attrs: &[Attribute]) -> impl Iterator<Item = &Attribute> {
+    attrs.iter().filter(|attr| attr.path().is_ident("repl"))
+}
+
+fn field_options(attrs: &[Attribute]) -> syn::Result<FieldOptions> {
+    let mut options = FieldOptions::default();
+    for attr in repl_attributes(attrs) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("long") {
+                options.long = Some(match meta.input.peek(Token![=]) {
+                    true => Some(meta.value()?.parse::<LitStr>()?.value()),
+                    false => None,
+                });
+            } else if meta.path.is_ident("short") {
+                options.short = Some(meta.value()?.parse::<LitChar>()?.value());
+            } else if meta.path.is_ident("default") {
+                options.default = Some(meta.value()?.parse::<LitStr>()?.value());
+            } else if meta.path.is_ident("choices") {
+                let content;
+                syn::parenthesized!(content in meta.input);
+                let choices = Punctuated::<LitStr, Token![,]>::parse_terminated(&content)?;
+                options.choices = choices.iter().map(LitStr::value).collect();
+            } else {
+                return Err(meta.error("expected `long`, `short`, `default` or `choices`"));
+            }
+            Ok(())
+        })?;
+    }
+
+    Ok(options)
+}
+
+// Join the lines of the doc comments, if there are any
+fn doc_comment(attrs: &[Attribute]) -> Option<String> {
+    let lines = attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("doc"))
+        .filter_map(|attr| match &attr.meta.require_name_value().ok()?.value {
+            Expr::Lit(expr) => match &expr.lit {
+                Lit::Str(line) => Some(line.value().trim().to_string()),
+                _ => None,
+            },
+            _ => None,
+        })
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<String>>();
+
+    match lines.is_empty() {
+        true => None,
+        false => Some(lines.join(" ")),
+    }
+}
+
+// Work out how a field's values are given from its type, and the type of each value
+fn shape(ty: &Type) -> (Shape, &Type) {
+    if let Some(inner) = generic_argument(ty, "Option") {
+        (Shape::Optional, inner)
+    } else if let Some(inner) = generic_argument(ty, "Vec") {
+        (Shape::List, inner)
+    } else if type_name(ty).as_deref() == Some("bool") {
+        (Shape::Flag, ty)
+    } else {
+        (Shape::Single, ty)
+    }
+}
+
+// The type argument of `wrapper<T>`, if that's what the type is
+fn generic_argument<'a>(ty: &'a Type, wrapper: &str) -> Option<&'a Type> {
+    let segment = match ty {
+        Type::Path(path) if path.qself.is_none() => path.path.segments.last()?,
+        _ => return None,
+    };
+    if segment.ident != wrapper {
+        return None;
+    }
+    match &segment.arguments {
+        PathArguments::AngleBracketed(arguments) => match arguments.args.first()? {
+            GenericArgument::Type(inner) => Some(inner),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn type_name(ty: &Type) -> Option<String> {
+    match ty {
+        Type::Path(path) => path
+            .path
+            .segments
+            .last()
+            .map(|segment| segment.ident.to_string()),
+        _ => None,
+    }
+}
+
+// Integer types, which Value converts to
+const INTEGER_TYPES: [&str; 12] = [
+    "i8", "i16", "i32", "i64", "i128", "isize", "u8", "u16", "u32", "u64", "u128", "usize",
+];
+
+// The ParameterType for values of the given type. Integers other than `i64` are checked
+// against their own type, so that out of range values are rejected before they're converted, and
+// types repl-rs doesn't know are checked by parsing them.
+fn parameter_type(ty: &Type) -> TokenStream2 {
+    match type_name(ty).as_deref() {
+        Some("i64") => quote!(::repl_rs::ParameterType::Int),
+        Some(name) if INTEGER_TYPES.contains(&name) => {
+            quote!(::repl_rs::ParameterType::custom::<#ty>("int"))
+        }
+        Some("f32" | "f64") => quote!(::repl_rs::ParameterType::Float),
+        Some("bool") => quote!(::repl_rs::ParameterType::Bool),
+        Some("PathBuf") => quote!(::repl_rs::ParameterType::Path),
+        Some("String") => quote!(::repl_rs::ParameterType::String),
+        name => {
+            let name = name.map_or_else(|| quote!(#ty).to_string(), str::to_string);
+            quote!(::repl_rs::ParameterType::custom::<#ty>(#name))
+        }
+    }
+}
+
+// Whether Value converts to the given type, rather than it having to be parsed
+fn is_convertible(ty: &Type) -> bool {
+    match type_name(ty).as_deref() {
+        Some("f32" | "f64" | "bool" | "PathBuf" | "String") => true,
+        Some(name) => INTEGER_TYPES.contains(&name),
+        None => false,
+    }
+}
+
+// Turn a struct name like `AddUser` into a command name like `add-user`
+fn kebab_case(name: &str) -> String {
+    let mut kebab = String::new();
+    for (index, c) in name.chars().enumerate() {
+        if c.is_uppercase() && index > 0 {
+            kebab.push('-');
+        }
+        kebab.extend(c.to_lowercase());
+    }
+
+    kebab
+}
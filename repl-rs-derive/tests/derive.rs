@@ -0,0 +1,154 @@
+use repl_rs::{Command, Error, Parameter, ParameterType, Repl, ReplCommand, Result};
+use std::cell::RefCell;
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+/// Grant a role
+/// to some users
+#[derive(Debug, PartialEq, ReplCommand)]
+struct GrantRole {
+    /// Role to grant
+    #[repl(choices("admin", "reader"))]
+    role: String,
+
+    /// Users to grant it to
+    users: Vec<String>,
+
+    /// Days until it expires
+    #[repl(short = 'd', long, default = "30")]
+    expires_in: usize,
+
+    /// Audit log
+    #[repl(long = "log")]
+    log_file: Option<PathBuf>,
+
+    #[repl(long)]
+    tag: Vec<String>,
+
+    /// Server to grant it on
+    #[repl(long)]
+    server: Option<IpAddr>,
+
+    /// Don't send emails
+    no_email: bool,
+}
+
+#[derive(ReplCommand)]
+#[repl(name = "ls")]
+struct List {
+    dir: Option<String>,
+}
+
+#[test]
+fn test_parameters() -> Result<()> {
+    assert_eq!("grant-role", GrantRole::name());
+    assert_eq!(Some("Grant a role to some users".into()), GrantRole::help());
+    assert_eq!(
+        vec![
+            Parameter::new("role")
+                .set_required(true)?
+                .set_choices(&["admin", "reader"])?
+                .with_help("Role to grant"),
+            Parameter::new("users")
+                .set_variadic(0, None)?
+                .with_help("Users to grant it to"),
+            Parameter::new("expires_in")
+                .set_long("expires-in")
+                .set_short('d')
+                .set_type(ParameterType::custom::<usize>("int"))?
+                .set_default("30")?
+                .with_help("Days until it expires"),
+            Parameter::new("log_file")
+                .set_long("log")
+                .set_type(ParameterType::Path)?
+                .with_help("Audit log"),
+            Parameter::new("tag").set_long("tag").set_multiple(true),
+            Parameter::new("server")
+                .set_long("server")
+                .set_type(ParameterType::custom::<IpAddr>("IpAddr"))?
+                .with_help("Server to grant it on"),
+            Parameter::new("no_email")
+                .set_flag(true)?
+                .with_help("Don't send emails"),
+        ],
+        GrantRole::parameters()?
+    );
+    assert_eq!("ls", List::name());
+    assert_eq!(None, List::help());
+
+    Ok(())
+}
+
+#[test]
+fn test_typed_command() -> Result<()> {
+    let granted = Rc::new(RefCell::new(vec![]));
+    let recorded = granted.clone();
+    let mut repl = Repl::new(())
+        .add_command(Command::typed(
+            move |args: GrantRole, _context: &mut ()| -> Result<Option<String>> {
+                recorded.borrow_mut().push(args);
+                Ok(None)
+            },
+        )?)
+        .add_command(Command::typed(
+            |args: List, _context: &mut ()| -> Result<Option<String>> { Ok(args.dir) },
+        )?);
+
+    repl.execute(
+        "grant-role admin alice bob -d 7 --log audit.log --tag a --tag b --server 10.0.0.1 \
+         --no-email",
+    )?;
+    repl.execute("grant-role reader")?;
+    assert_eq!(
+        vec![
+            GrantRole {
+                role: "admin".into(),
+                users: vec!["alice".into(), "bob".into()],
+                expires_in: 7,
+                log_file: Some("audit.log".into()),
+                tag: vec!["a".into(), "b".into()],
+                server: Some([10, 0, 0, 1].into()),
+                no_email: true,
+            },
+            GrantRole {
+                role: "reader".into(),
+                users: vec![],
+                expires_in: 30,
+                log_file: None,
+                tag: vec![],
+                server: None,
+                no_email: false,
+            },
+        ],
+        *granted.borrow()
+    );
+    assert_eq!(Ok(Some("/tmp".into())), repl.execute("ls /tmp"));
+    assert_eq!(Ok(None), repl.execute("ls"));
+    assert_eq!(
+        Err(Error::InvalidArgument(
+            "expires_in".into(),
+            "int".into(),
+            "soon".into()
+        )),
+        repl.execute("grant-role admin --expires-in soon")
+    );
+    assert_eq!(
+        Err(Error::InvalidArgument(
+            "expires_in".into(),
+            "int".into(),
+            "-1".into()
+        )),
+        repl.execute("grant-role admin --expires-in -1")
+    );
+    assert_eq!(
+        Err(Error::InvalidArgument(
+            "server".into(),
+            "IpAddr".into(),
+            "localhost".into()
+        )),
+        repl.execute("grant-role admin --server localhost")
+    );
+
+    Ok(())
+}
@@ -0,0 +1,86 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+// Aliases defined at runtime with the built-in `alias` command, each of which expands to a
+// command and possibly some arguments for it. This is shared with the line editor's helper, so
+// the aliases can be completed with tab as soon as they're defined.
+#[derive(Clone, Default)]
+pub(crate) struct Aliases(Rc<RefCell<BTreeMap<String, Vec<String>>>>);
+
+impl Aliases {
+    pub(crate) fn insert(&self, name: &str, expansion: Vec<String>) {
+        self.0.borrow_mut().insert(name.to_string(), expansion);
+    }
+
+    pub(crate) fn get(&self, name: &str) -> Option<Vec<String>> {
+        self.0.borrow().get(name).cloned()
+    }
+
+    pub(crate) fn names(&self) -> Vec<String> {
+        self.0.borrow().keys().cloned().collect()
+    }
+
+    // Each alias along with what it expands to, as it would be typed, sorted by name
+    pub(crate) fn describe(&self) -> Vec<(String, String)> {
+        self.0
+            .borrow()
+            .iter()
+            .map(|(name, expansion)| {
+                let words = expansion.iter().map(|word| quote(word));
+                (name.clone(), words.collect::<Vec<String>>().join(" "))
+            })
+            .collect()
+    }
+
+    // If the first argument is an alias, replace it with what it expands to. Aliases aren't
+    // expanded again, so they can't loop.
+    pub(crate) fn expand(&self, mut args: Vec<String>) -> Vec<String> {
+        if let Some(expansion) = args.first().and_then(|first| self.get(first)) {
+            args.splice(..1, expansion);
+        }
+
+        args
+    }
+}
+
+// Quote a word, if needed, so that it would be read back as the same argument
+fn quote(word: &str) -> String {
+    if word.is_empty() || word.contains(|c: char| c.is_whitespace() || "'\"\\".contains(c)) {
+        format!("'{}'", word.replace('\'', r"'\''"))
+    } else {
+        word.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::alias::Aliases;
+
+    fn words(words: &[&str]) -> Vec<String> {
+        words.iter().map(|word| word.to_string()).collect()
+    }
+
+    #[test]
+    fn test_expand() {
+        let aliases = Aliases::default();
+        aliases.insert("ll", words(&["list", "--long"]));
+        aliases.insert("greet", words(&["say", "hello world"]));
+
+        assert_eq!(
+            words(&["list", "--long", "/tmp"]),
+            aliases.expand(words(&["ll", "/tmp"]))
+        );
+        assert_eq!(
+            words(&["list", "ll"]),
+            aliases.expand(words(&["list", "ll"]))
+        );
+        assert_eq!(
+            vec![
+                ("greet".to_string(), "say 'hello world'".to_string()),
+                ("ll".to_string(), "list --long".to_string())
+            ],
+            aliases.describe()
+        );
+    }
+}
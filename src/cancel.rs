@@ -0,0 +1,225 @@
+#[cfg(unix)]
+use std::os::unix::io::RawFd;
+#[cfg(unix)]
+use std::sync::atomic::AtomicI32;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+#[cfg(feature = "async")]
+use std::sync::Mutex;
+#[cfg(feature = "async")]
+use std::task::{Poll, Waker};
+
+/// Token which lets long-running commands notice that the user has pressed Ctrl-C, so they can
+/// stop what they're doing. Get one from
+/// [Repl::cancellation_token](struct.Repl.html#method.cancellation_token), move it into the
+/// closure for your command, and check it every so often:
+/// ```
+/// use repl_rs::{Command, Repl, Result, Value};
+/// use std::collections::HashMap;
+///
+/// let repl = Repl::<(), repl_rs::Error>::new(());
+/// let token = repl.cancellation_token();
+/// let repl = repl.add_command(Command::new(
+///     "count",
+///     move |_args: HashMap<String, Value>, _context: &mut ()| -> Result<Option<String>> {
+///         let mut count = 0u64;
+///         while !token.is_cancelled() && count < 1_000_000 {
+///             count += 1;
+///         }
+///         Ok(Some(count.to_string()))
+///     },
+/// ));
+/// ```
+/// The token is reset before each command is run.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+    #[cfg(feature = "async")]
+    wakers: Arc<Mutex<Vec<Waker>>>,
+}
+
+impl CancellationToken {
+    /// Whether the running command has been cancelled
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Cancel the running command. This is what Ctrl-C does while a command is running.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+        #[cfg(feature = "async")]
+        for waker in self.wakers.lock().unwrap().drain(..) {
+            waker.wake();
+        }
+    }
+
+    /// Wait until the running command is cancelled. Async commands are dropped when this
+    /// happens anyway, so you'll only need it for work that outlives the command, such as
+    /// spawned tasks.
+    #[cfg(feature = "async")]
+    pub async fn cancelled(&self) {
+        std::future::poll_fn(|context| {
+            if !self.is_cancelled() {
+                let mut wakers = self.wakers.lock().unwrap();
+                if !wakers.iter().any(|waker| waker.will_wake(context.waker())) {
+                    wakers.push(context.waker().clone());
+                }
+            }
+            // Check again now the waker is registered, in case it was cancelled in between
+            match self.is_cancelled() {
+                true => Poll::Ready(()),
+                false => Poll::Pending,
+            }
+        })
+        .await
+    }
+
+    pub(crate) fn reset(&self) {
+        self.cancelled.store(false, Ordering::SeqCst);
+        #[cfg(feature = "async")]
+        self.wakers.lock().unwrap().clear();
+    }
+}
+
+// While a Repl is running in the terminal, Ctrl-C cancels its token rather than killing the
+// process. The line editor reads Ctrl-C as a key while it's reading a line, so in practice the
+// signal only arrives while a command is running. The signal handler writes to a pipe, and a
+// thread belonging to the session cancels the session's token when it reads from it, since
+// hardly anything is safe to do in a signal handler. The handler is only installed while the
+// session lasts, and the previous disposition is restored when it ends.
+#[cfg(unix)]
+pub(crate) struct InterruptHandler {
+    previous: libc::sigaction,
+    pipe: (RawFd, RawFd),
+    thread: Option<std::thread::JoinHandle<()>>,
+}
+
+// Write end of the pipe of the session whose handler is installed, or -1 if there isn't one
+#[cfg(unix)]
+static INTERRUPT_PIPE: AtomicI32 = AtomicI32::new(-1);
+
+#[cfg(unix)]
+extern "C" fn on_interrupt(_signal: libc::c_int) {
+    let pipe = INTERRUPT_PIPE.load(Ordering::SeqCst);
+    if pipe >= 0 {
+        unsafe { libc::write(pipe, b"c".as_ptr() as *const libc::c_void, 1) };
+    }
+}
+
+#[cfg(unix)]
+impl InterruptHandler {
+    // Install the handler for a session, unless the application has a SIGINT handler of its
+    // own, or another session already has one installed, in which case they're left alone
+    pub(crate) fn install(token: &CancellationToken) -> Option<Self> {
+        unsafe {
+            let mut previous: libc::sigaction = std::mem::zeroed();
+            if libc::sigaction(libc::SIGINT, std::ptr::null(), &mut previous) != 0
+                || previous.sa_sigaction != libc::SIG_DFL
+            {
+                return None;
+            }
+            let mut fds = [-1; 2];
+            if libc::pipe(fds.as_mut_ptr()) != 0 {
+                return None;
+            }
+            if INTERRUPT_PIPE
+                .compare_exchange(-1, fds[1], Ordering::SeqCst, Ordering::SeqCst)
+                .is_err()
+            {
+                libc::close(fds[0]);
+                libc::close(fds[1]);
+                return None;
+            }
+            let token = token.clone();
+            let read = fds[0];
+            let thread = std::thread::spawn(move || {
+                let mut byte = 0u8;
+                while libc::read(read, &mut byte as *mut u8 as *mut libc::c_void, 1) == 1
+                    && byte == b'c'
+                {
+                    token.cancel();
+                }
+            });
+            let mut action: libc::sigaction = std::mem::zeroed();
+            action.sa_sigaction = on_interrupt as extern "C" fn(libc::c_int) as libc::sighandler_t;
+            action.sa_flags = libc::SA_RESTART;
+            libc::sigemptyset(&mut action.sa_mask);
+            libc::sigaction(libc::SIGINT, &action, &mut previous);
+
+            Some(Self {
+                previous,
+                pipe: (fds[0], fds[1]),
+                thread: Some(thread),
+            })
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for InterruptHandler {
+    fn drop(&mut self) {
+        unsafe {
+            libc::sigaction(libc::SIGINT, &self.previous, std::ptr::null_mut());
+            INTERRUPT_PIPE.store(-1, Ordering::SeqCst);
+            // Anything other than a 'c' stops the thread
+            libc::write(self.pipe.1, b"q".as_ptr() as *const libc::c_void, 1);
+            if let Some(thread) = self.thread.take() {
+                let _ = thread.join();
+            }
+            libc::close(self.pipe.0);
+            libc::close(self.pipe.1);
+        }
+    }
+}
+
+// Ctrl-C isn't caught on other platforms, so it ends the process as usual
+#[cfg(not(unix))]
+pub(crate) struct InterruptHandler;
+
+#[cfg(not(unix))]
+impl InterruptHandler {
+    pub(crate) fn install(_token: &CancellationToken) -> Option<Self> {
+        None
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use crate::cancel::{CancellationToken, InterruptHandler};
+
+    // Puts back the SIGINT disposition the test process had, even if the test fails
+    struct RestoreDisposition(libc::sigaction);
+
+    impl Drop for RestoreDisposition {
+        fn drop(&mut self) {
+            unsafe { libc::sigaction(libc::SIGINT, &self.0, std::ptr::null_mut()) };
+        }
+    }
+
+    #[test]
+    fn test_interrupt_handler() {
+        let mut previous: libc::sigaction = unsafe { std::mem::zeroed() };
+        let mut default: libc::sigaction = unsafe { std::mem::zeroed() };
+        default.sa_sigaction = libc::SIG_DFL;
+        unsafe { libc::sigaction(libc::SIGINT, &default, &mut previous) };
+        let _restore = RestoreDisposition(previous);
+
+        let token = CancellationToken::default();
+        // Without the handler, raising SIGINT below would end the test process
+        let handler = InterruptHandler::install(&token).expect("the handler should be installed");
+        // Only one session can have the handler at a time
+        assert!(InterruptHandler::install(&CancellationToken::default()).is_none());
+
+        unsafe { libc::raise(libc::SIGINT) };
+        let start = std::time::Instant::now();
+        while !token.is_cancelled() && start.elapsed() < std::time::Duration::from_secs(5) {
+            std::thread::yield_now();
+        }
+        assert!(token.is_cancelled());
+
+        drop(handler);
+        let mut current: libc::sigaction = unsafe { std::mem::zeroed() };
+        unsafe { libc::sigaction(libc::SIGINT, std::ptr::null(), &mut current) };
+        assert_eq!(libc::SIG_DFL, current.sa_sigaction);
+    }
+}
@@ -0,0 +1,375 @@
+use crate::command::RawHandler;
+use crate::error::*;
+use crate::{Command, Parameter, ParameterType, Repl};
+use clap::error::ErrorKind;
+use clap::{Arg, ArgAction, ArgMatches, ValueHint};
+use std::any::TypeId;
+use std::cell::RefCell;
+use std::fmt::Display;
+use std::rc::Rc;
+
+impl<Context, E> Command<Context, E> {
+    /// Create a command from a [clap](https://docs.rs/clap) command definition, so you can share
+    /// one definition between your command line and your REPL. Each line is parsed by clap, and
+    /// the handler is passed the resulting `ArgMatches`, including those of any subcommand. The
+    /// clap command's subcommands, arguments, aliases and help are used for the REPL's help and
+    /// completion, and `--help` and `--version` print clap's help and version as usual.
+    pub fn from_clap<F>(mut command: clap::Command, handler: F) -> Self
+    where
+        E: From<Error>,
+        F: 'static + FnMut(ArgMatches, &mut Context) -> std::result::Result<Option<String>, E>,
+    {
+        command.build();
+        let mut definition = describe(&command);
+        let words = vec![command.get_name().to_string()];
+        definition.raw_handler = Some(clap_handler(command, words, handler));
+
+        definition
+    }
+}
+
+impl<Context, E> Repl<Context, E>
+where
+    E: Display + From<Error>,
+{
+    /// Add each of the [clap](https://docs.rs/clap) command's subcommands as a command of its
+    /// own, so a program whose command line is a set of subcommands, like `git` or `cargo`, can
+    /// run them at the prompt without the program's name in front. Each line is parsed by the
+    /// whole clap command, as if the program's name came first, so the handler is passed the
+    /// same `ArgMatches` as when the program is run from the command line, and can tell which
+    /// subcommand was run from `matches.subcommand()`.
+    pub fn add_clap_subcommands<F>(mut self, mut command: clap::Command, handler: F) -> Self
+    where
+        F: 'static + FnMut(ArgMatches, &mut Context) -> std::result::Result<Option<String>, E>,
+    {
+        command.build();
+        let handler = Rc::new(RefCell::new(handler));
+        for subcommand in subcommands(&command) {
+            let mut definition = describe(subcommand);
+            let words = vec![
+                command.get_name().to_string(),
+                subcommand.get_name().to_string(),
+            ];
+            let handler = handler.clone();
+            definition.raw_handler = Some(clap_handler(
+                command.clone(),
+                words,
+                move |matches: ArgMatches, context: &mut Context| {
+                    (handler.borrow_mut())(matches, context)
+                },
+            ));
+            self = self.add_command(definition);
+        }
+
+        self
+    }
+}
+
+// Handler which parses the arguments with the clap command, after the words which lead to the
+// command being run, and passes the matches on
+fn clap_handler<Context, E, F>(
+    mut command: clap::Command,
+    words: Vec<String>,
+    mut handler: F,
+) -> RawHandler<Context, E>
+where
+    E: From<Error>,
+    F: 'static + FnMut(ArgMatches, &mut Context) -> std::result::Result<Option<String>, E>,
+{
+    Box::new(move |args: &[&str], context: &mut Context| {
+        let args = words.iter().map(String::as_str).chain(args.iter().copied());
+        match command.try_get_matches_from_mut(args) {
+            Ok(matches) => handler(matches, context),
+            Err(error)
+                if matches!(
+                    error.kind(),
+                    ErrorKind::DisplayHelp | ErrorKind::DisplayVersion
+                ) =>
+            {
+                Ok(Some(error.to_string().trim_end().to_string()))
+            }
+            Err(error) => Err(Error::from(error).into()),
+        }
+    })
+}
+
+// Describe a clap command, and its subcommands, as a command without a handler, for the help
+// and completion
+fn describe<Context, E>(command: &clap::Command) -> Command<Context, E> {
+    let mut definition = Command::group(command.get_name());
+    definition.aliases = command.get_all_aliases().map(String::from).collect();
+    definition.help_summary = command.get_about().map(|about| about.to_string());
+    definition.parameters = command
+        .get_arguments()
+        .filter(|arg| !arg.is_hide_set())
+        .filter(|arg| {
+            !matches!(
+                arg.get_action(),
+                ArgAction::Help | ArgAction::HelpShort | ArgAction::HelpLong | ArgAction::Version
+            )
+        })
+        .map(parameter)
+        .collect();
+    for subcommand in subcommands(command) {
+        definition = definition.with_subcommand(describe(subcommand));
+    }
+
+    definition
+}
+
+// The subcommands shown to the REPL, leaving the help subcommand clap adds to the REPL's own help
+fn subcommands(command: &clap::Command) -> impl Iterator<Item = &clap::Command> {
+    command
+        .get_subcommands()
+        .filter(|sub| !sub.is_hide_set() && sub.get_name() != "help")
+}
+
+fn parameter(arg: &Arg) -> Parameter {
+    let name = arg.get_id().as_str();
+    if !arg.get_action().takes_values() {
+        return Parameter {
+            long: arg.get_long().map(String::from),
+            short: arg.get_short(),
+            kind: ParameterType::Bool,
+            flag: true,
+            help: arg.get_help().map(|help| help.to_string()),
+            ..Parameter::new(name)
+        };
+    }
+
+    let values = arg.get_num_args().unwrap_or_default();
+    let multiple = values.max_values() > 1 || matches!(arg.get_action(), ArgAction::Append);
+    let max = Some(values.max_values()).filter(|max| *max != usize::MAX);
+    Parameter {
+        required: arg.is_required_set(),
+        default: arg
+            .get_default_values()
+            .first()
+            .map(|default| default.to_string_lossy().into_owned()),
+        kind: parameter_type(arg),
+        long: arg.get_long().map(String::from),
+        short: arg.get_short(),
+        multiple: multiple && !arg.is_positional(),
+        variadic: (multiple && arg.is_positional()).then(|| (values.min_values(), max)),
+        choices: arg
+            .get_possible_values()
+            .iter()
+            .filter(|value| !value.is_hide_set())
+            .map(|value| value.get_name().to_string())
+            .collect(),
+        help: arg.get_help().map(|help| help.to_string()),
+        ..Parameter::new(name)
+    }
+}
+
+// The parameter type matching the argument's value parser, so the help shows the kind of value
+// it takes and paths are completed with tab
+fn parameter_type(arg: &Arg) -> ParameterType {
+    let is = |types: &[TypeId]| {
+        types
+            .iter()
+            .any(|id| arg.get_value_parser().type_id() == *id)
+    };
+    if matches!(
+        arg.get_value_hint(),
+        ValueHint::AnyPath | ValueHint::FilePath | ValueHint::DirPath
+    ) {
+        ParameterType::Path
+    } else if is(&[
+        TypeId::of::<i8>(),
+        TypeId::of::<i16>(),
+        TypeId::of::<i32>(),
+        TypeId::of::<i64>(),
+        TypeId::of::<i128>(),
+        TypeId::of::<isize>(),
+        TypeId::of::<u8>(),
+        TypeId::of::<u16>(),
+        TypeId::of::<u32>(),
+        TypeId::of::<u64>(),
+        TypeId::of::<u128>(),
+        TypeId::of::<usize>(),
+    ]) {
+        ParameterType::Int
+    } else if is(&[TypeId::of::<f32>(), TypeId::of::<f64>()]) {
+        ParameterType::Float
+    } else if is(&[TypeId::of::<bool>()]) {
+        ParameterType::Bool
+    } else {
+        ParameterType::String
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::clap_command::parameter;
+    use crate::error::*;
+    use crate::{Command, Parameter, ParameterType, Repl};
+    use clap::{value_parser, Arg, ArgAction, ArgMatches};
+    use std::path::PathBuf;
+
+    fn remote() -> clap::Command {
+        clap::Command::new("remote")
+            .about("Manage remotes")
+            .version("1.0")
+            .subcommand(
+                clap::Command::new("add")
+                    .about("Add a remote")
+                    .visible_alias("new")
+                    .arg(Arg::new("name").required(true).help("Name of the remote"))
+                    .arg(Arg::new("url").required(true))
+                    .arg(
+                        Arg::new("timeout")
+                            .short('t')
+                            .long("timeout")
+                            .value_parser(value_parser!(u32))
+                            .default_value("30"),
+                    ),
+            )
+            .subcommand(
+                clap::Command::new("remove")
+                    .arg(
+                        Arg::new("names")
+                            .required(true)
+                            .num_args(1..)
+                            .value_parser(value_parser!(PathBuf)),
+                    )
+                    .arg(Arg::new("force").short('f').action(ArgAction::SetTrue))
+                    .arg(
+                        Arg::new("mode")
+                            .long("mode")
+                            .value_parser(["all", "some"])
+                            .action(ArgAction::Append),
+                    ),
+            )
+    }
+
+    fn describe_matches(matches: ArgMatches, _context: &mut ()) -> Result<Option<String>> {
+        Ok(match matches.subcommand() {
+            Some(("add", matches)) => Some(format!(
+                "{} {} {}",
+                matches.get_one::<String>("name").unwrap(),
+                matches.get_one::<String>("url").unwrap(),
+                matches.get_one::<u32>("timeout").unwrap(),
+            )),
+            Some(("remove", matches)) => Some(format!(
+                "{:?} {}",
+                matches
+                    .get_many::<PathBuf>("names")
+                    .unwrap_or_default()
+                    .collect::<Vec<&PathBuf>>(),
+                matches.get_flag("force"),
+            )),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn test_describe() -> Result<()> {
+        let command = Command::<(), Error>::from_clap(remote(), describe_matches);
+        assert_eq!("remote", command.name);
+        assert_eq!(Some("Manage remotes".into()), command.help_summary);
+
+        assert!(!command.subcommands.contains_key("help"));
+        let add = &command.subcommands["add"];
+        assert_eq!(vec!["new".to_string()], add.aliases);
+        assert_eq!(
+            vec![
+                Parameter::new("name")
+                    .set_required(true)?
+                    .with_help("Name of the remote"),
+                Parameter::new("url").set_required(true)?,
+                Parameter::new("timeout")
+                    .set_short('t')
+                    .set_long("timeout")
+                    .set_type(ParameterType::Int)?
+                    .set_default("30")?,
+            ],
+            add.parameters
+        );
+        assert_eq!(
+            vec![
+                Parameter::new("names")
+                    .set_type(ParameterType::Path)?
+                    .set_variadic(1, None)?,
+                Parameter::new("force").set_short('f').set_flag(true)?,
+                Parameter::new("mode")
+                    .set_long("mode")
+                    .set_multiple(true)
+                    .set_choices(&["all", "some"])?,
+            ],
+            command.subcommands["remove"].parameters
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_execute() {
+        let mut repl = Repl::new(()).add_command(Command::from_clap(remote(), describe_matches));
+
+        assert_eq!(
+            Ok(Some("origin https://example.com 30".into())),
+            repl.execute("remote add origin https://example.com")
+        );
+        assert_eq!(
+            Ok(Some("upstream git@example.com 5".into())),
+            repl.execute("remote new upstream git@example.com -t 5")
+        );
+        assert_eq!(
+            Ok(Some("[\"a\", \"b\"] true".into())),
+            repl.execute("remote remove -f a b")
+        );
+        assert_eq!(
+            Ok(Some("remote 1.0".into())),
+            repl.execute("remote --version")
+        );
+        assert_eq!(
+            Ok(Some(
+                "remote: Manage remotes\nUsage:\n\tremote <subcommand>\nSubcommands:\n\
+                 \tadd (new) - Add a remote\n\tremove"
+                    .into()
+            )),
+            repl.execute("help remote")
+        );
+        let help = repl.execute("remote add --help").unwrap().unwrap();
+        assert!(help.starts_with("Add a remote\n\nUsage: remote add"));
+        match repl.execute("remote add origin") {
+            Err(Error::ClapError(message)) => {
+                assert!(message
+                    .starts_with("the following required arguments were not provided:\n  <url>"))
+            }
+            result => panic!("Unexpected result {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_subcommands() {
+        let mut repl = Repl::new(()).add_clap_subcommands(remote(), describe_matches);
+
+        assert_eq!(
+            Ok(Some("origin https://example.com 30".into())),
+            repl.execute("add origin https://example.com")
+        );
+        assert_eq!(
+            Ok(Some("upstream git@example.com 5".into())),
+            repl.execute("new upstream git@example.com -t 5")
+        );
+        assert_eq!(Ok(Some("[\"a\"] false".into())), repl.execute("remove a"));
+        let help = repl.execute("help").unwrap().unwrap();
+        assert_eq!(
+            vec!["add (new) - Add a remote", "remove"],
+            help.lines().skip(2).collect::<Vec<&str>>()
+        );
+    }
+
+    #[test]
+    fn test_integer_types() {
+        for arg in [
+            Arg::new("a").value_parser(value_parser!(i128)),
+            Arg::new("b").value_parser(value_parser!(u128)),
+            Arg::new("c").value_parser(value_parser!(isize)),
+        ] {
+            assert_eq!(ParameterType::Int, parameter(&arg).kind);
+        }
+    }
+}
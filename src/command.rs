@@ -1,22 +1,120 @@
 use crate::error::*;
-use crate::Callback;
 use crate::Parameter;
+use crate::Value;
+use std::collections::HashMap;
 use std::fmt;
+use std::rc::Rc;
+
+/// Function which completes a parameter's value with tab, given the context and the partial
+/// value typed so far
+pub(crate) type Completer<Context> = Rc<dyn Fn(&Context, &str) -> Vec<String>>;
+
+/// Function which parses a command's arguments itself and runs the command, given the arguments
+/// as they were typed, rather than checked against the command's parameters
+pub(crate) type RawHandler<Context, E> =
+    Box<dyn FnMut(&[&str], &mut Context) -> std::result::Result<Option<String>, E>>;
+
+/// Future returned by an async command's handler, which can borrow the context until it's done
+#[cfg(feature = "async")]
+pub type CommandFuture<'a, E> = std::pin::Pin<
+    Box<dyn std::future::Future<Output = std::result::Result<Option<String>, E>> + 'a>,
+>;
+
+/// Handler of an async command, called with the validated arguments and the Repl context
+#[cfg(feature = "async")]
+pub(crate) type AsyncHandler<Context, E> =
+    Box<dyn for<'a> FnMut(HashMap<String, Value>, &'a mut Context) -> CommandFuture<'a, E>>;
+
+/// Trait for anything which can be called when a command is run.
+///
+/// This is implemented for any function or closure with the
+/// [Callback](type.Callback.html) signature, so plain `fn`s and closures capturing
+/// configuration or handles can be passed straight to
+/// [Command::new](struct.Command.html#method.new).
+/// You can also implement it for your own structs:
+/// ```
+/// use repl_rs::{CommandHandler, Result, Value};
+/// use std::collections::HashMap;
+///
+/// struct Counter {
+///     count: usize,
+/// }
+///
+/// impl<Context> CommandHandler<Context, repl_rs::Error> for Counter {
+///     fn handle(
+///         &mut self,
+///         _args: HashMap<String, Value>,
+///         _context: &mut Context,
+///     ) -> Result<Option<String>> {
+///         self.count += 1;
+///         Ok(Some(self.count.to_string()))
+///     }
+/// }
+/// ```
+pub trait CommandHandler<Context, E> {
+    /// Called with the validated arguments and the Repl context when the command is run
+    fn handle(
+        &mut self,
+        args: HashMap<String, Value>,
+        context: &mut Context,
+    ) -> std::result::Result<Option<String>, E>;
+}
+
+impl<Context, E, F> CommandHandler<Context, E> for F
+where
+    F: FnMut(HashMap<String, Value>, &mut Context) -> std::result::Result<Option<String>, E>,
+{
+    fn handle(
+        &mut self,
+        args: HashMap<String, Value>,
+        context: &mut Context,
+    ) -> std::result::Result<Option<String>, E> {
+        self(args, context)
+    }
+}
+
+/// Trait for a struct holding a command's arguments, which describes the command and its
+/// parameters, and converts the validated arguments into the struct. Rather than implementing
+/// it yourself, you'll usually derive it with `#[derive(ReplCommand)]`, which needs the `derive`
+/// feature. Commands are made from it with [Command::typed](struct.Command.html#method.typed).
+pub trait ReplCommand: Sized {
+    /// Name of the command
+    fn name() -> String;
+
+    /// Help summary for the command
+    fn help() -> Option<String>;
+
+    /// The command's parameters, in the order they're added to the command
+    fn parameters() -> Result<Vec<Parameter>>;
+
+    /// Convert the validated arguments into the struct
+    fn from_args(args: &HashMap<String, Value>) -> Result<Self>;
+}
 
 /// Struct to define a command in the REPL
 pub struct Command<Context, E> {
     pub(crate) name: String,
+    pub(crate) aliases: Vec<String>,
     pub(crate) parameters: Vec<Parameter>,
-    pub(crate) callback: Callback<Context, E>,
+    pub(crate) handler: Option<Box<dyn CommandHandler<Context, E>>>,
+    pub(crate) raw_handler: Option<RawHandler<Context, E>>,
+    #[cfg(feature = "async")]
+    pub(crate) async_handler: Option<AsyncHandler<Context, E>>,
     pub(crate) help_summary: Option<String>,
+    pub(crate) body: Option<String>,
+    pub(crate) subcommands: HashMap<String, Command<Context, E>>,
+    pub(crate) completers: HashMap<String, Completer<Context>>,
 }
 
 impl<Context, E> fmt::Debug for Command<Context, E> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("Command")
             .field("name", &self.name)
+            .field("aliases", &self.aliases)
             .field("parameters", &self.parameters)
             .field("help_summary", &self.help_summary)
+            .field("body", &self.body)
+            .field("subcommands", &self.subcommands)
             .finish()
     }
 }
@@ -24,26 +122,151 @@ impl<Context, E> fmt::Debug for Command<Context, E> {
 impl<Context, E> std::cmp::PartialEq for Command<Context, E> {
     fn eq(&self, other: &Command<Context, E>) -> bool {
         self.name == other.name
+            && self.aliases == other.aliases
             && self.parameters == other.parameters
             && self.help_summary == other.help_summary
+            && self.body == other.body
+            && self.subcommands == other.subcommands
     }
 }
 
 impl<Context, E> Command<Context, E> {
-    /// Create a new command with the given name and callback function
-    pub fn new(name: &str, callback: Callback<Context, E>) -> Self {
+    /// Create a new command with the given name and handler. The handler can be a callback
+    /// function, a closure, or anything else implementing
+    /// [CommandHandler](trait.CommandHandler.html).
+    ///
+    /// Handlers aren't passed the Repl's [CancellationToken](struct.CancellationToken.html) or
+    /// [ExitHandle](struct.ExitHandle.html), so a handler which needs one captures it from the
+    /// Repl before the command is built:
+    /// ```
+    /// use repl_rs::{Command, Repl, Result, Value};
+    /// use std::collections::HashMap;
+    ///
+    /// let repl = Repl::new(());
+    /// let token = repl.cancellation_token();
+    /// let repl = repl.add_command(Command::new(
+    ///     "count",
+    ///     move |_args: HashMap<String, Value>, _context: &mut ()| -> Result<Option<String>> {
+    ///         let mut count = 0u64;
+    ///         while count < 1_000_000 && !token.is_cancelled() {
+    ///             count += 1;
+    ///         }
+    ///         Ok(Some(count.to_string()))
+    ///     },
+    /// ));
+    /// ```
+    pub fn new<H: CommandHandler<Context, E> + 'static>(name: &str, handler: H) -> Self {
+        Self {
+            name: name.to_string(),
+            aliases: vec![],
+            parameters: vec![],
+            handler: Some(Box::new(handler)),
+            raw_handler: None,
+            #[cfg(feature = "async")]
+            async_handler: None,
+            help_summary: None,
+            body: None,
+            subcommands: HashMap::new(),
+            completers: HashMap::new(),
+        }
+    }
+
+    /// Create a new async command with the given name and handler, which returns a future
+    /// rather than the command's result. The future can borrow the context, and is run by
+    /// [Repl::run_async](struct.Repl.html#method.run_async) or
+    /// [Repl::execute_async](struct.Repl.html#method.execute_async); it's dropped if Ctrl-C is
+    /// pressed before it's done.
+    /// ```
+    /// use repl_rs::{Command, Convert, Value};
+    /// use std::collections::HashMap;
+    /// use std::time::Duration;
+    ///
+    /// let command = Command::new_async(
+    ///     "wait",
+    ///     |args: HashMap<String, Value>, _context: &mut ()| {
+    ///         Box::pin(async move {
+    ///             let seconds: u64 = args["seconds"].convert()?;
+    ///             tokio::time::sleep(Duration::from_secs(seconds)).await;
+    ///             Ok(Some("Done".to_string()))
+    ///         })
+    ///     },
+    /// );
+    /// # let _: Command<(), repl_rs::Error> = command;
+    /// ```
+    #[cfg(feature = "async")]
+    pub fn new_async<F>(name: &str, handler: F) -> Self
+    where
+        F: 'static + for<'a> FnMut(HashMap<String, Value>, &'a mut Context) -> CommandFuture<'a, E>,
+    {
+        let mut command = Command::group(name);
+        command.async_handler = Some(Box::new(handler));
+
+        command
+    }
+
+    /// Create a command from a struct implementing [ReplCommand](trait.ReplCommand.html), which
+    /// gives its name, help and parameters. The handler is passed the arguments converted into
+    /// the struct, rather than a `HashMap`.
+    pub fn typed<A, F>(mut handler: F) -> Result<Self>
+    where
+        A: ReplCommand,
+        E: From<Error>,
+        F: 'static + FnMut(A, &mut Context) -> std::result::Result<Option<String>, E>,
+    {
+        let mut command = Command::new(
+            &A::name(),
+            move |args: HashMap<String, Value>, context: &mut Context| {
+                handler(A::from_args(&args)?, context)
+            },
+        );
+        for parameter in A::parameters()? {
+            command = command.with_parameter(parameter)?;
+        }
+        if let Some(help) = A::help() {
+            command = command.with_help(&help);
+        }
+
+        Ok(command)
+    }
+
+    /// Create a new command which does nothing itself, and only groups together its
+    /// subcommands, for instance the `user` in `user add` and `user list`
+    pub fn group(name: &str) -> Self {
         Self {
             name: name.to_string(),
+            aliases: vec![],
             parameters: vec![],
-            callback,
+            handler: None,
+            raw_handler: None,
+            #[cfg(feature = "async")]
+            async_handler: None,
             help_summary: None,
+            body: None,
+            subcommands: HashMap::new(),
+            completers: HashMap::new(),
         }
     }
 
-    /// Add a parameter to the command. The order of the parameters is the same as the order in
-    /// which this is called for each parameter.
+    /// Add a parameter to the command. The order of the positional parameters is the same as the
+    /// order in which this is called for each parameter. Named options can be given anywhere on
+    /// the line, in any order.
     pub fn with_parameter(mut self, parameter: Parameter) -> Result<Command<Context, E>> {
-        if parameter.required && self.parameters.iter().any(|param| !param.required) {
+        if parameter.variadic.is_some() && !parameter.is_positional()
+            || parameter.is_positional()
+                && self
+                    .parameters
+                    .iter()
+                    .any(|param| param.is_positional() && param.variadic.is_some())
+        {
+            return Err(Error::IllegalVariadicError(parameter.name));
+        }
+        if parameter.required
+            && parameter.is_positional()
+            && self
+                .parameters
+                .iter()
+                .any(|param| param.is_positional() && !param.required)
+        {
             return Err(Error::IllegalRequiredError(parameter.name));
         }
 
@@ -52,6 +275,96 @@ impl<Context, E> Command<Context, E> {
         Ok(self)
     }
 
+    /// Add a subcommand to the command. If the first argument to the command is the name of
+    /// one of its subcommands, the subcommand is run with the rest of the arguments instead
+    pub fn with_subcommand(mut self, subcommand: Command<Context, E>) -> Command<Context, E> {
+        self.subcommands.insert(subcommand.name.clone(), subcommand);
+
+        self
+    }
+
+    /// Let the command take a heredoc-style body: when its arguments end with `<<TERMINATOR`,
+    /// the lines after them, up to one containing only the terminator, are read as well and
+    /// passed to the handler as the value of the named argument. The value is left out when no
+    /// body is given.
+    pub fn with_body(mut self, name: &str) -> Command<Context, E> {
+        self.body = Some(name.to_string());
+
+        self
+    }
+
+    /// Whether the command, or the subcommand the arguments lead to, takes a body
+    pub(crate) fn takes_body(&self, args: &[&str]) -> bool {
+        let subcommand = args.first().and_then(|arg| {
+            self.subcommands
+                .values()
+                .find(|subcommand| subcommand.is_called(arg))
+        });
+        match subcommand {
+            Some(subcommand) => subcommand.takes_body(&args[1..]),
+            None => self.body.is_some(),
+        }
+    }
+
+    /// Add another name the command can be run as, for instance `ls` for `list`. Aliases are
+    /// shown in the help, and completed with tab.
+    pub fn with_alias(mut self, alias: &str) -> Command<Context, E> {
+        self.aliases.push(alias.to_string());
+
+        self
+    }
+
+    /// Whether the command is called the given name, or has it as an alias
+    pub(crate) fn is_called(&self, name: &str) -> bool {
+        self.name == name || self.aliases.iter().any(|alias| alias == name)
+    }
+
+    /// Names and aliases of the command's subcommands
+    pub(crate) fn subcommand_names(&self) -> impl Iterator<Item = &str> {
+        self.subcommands.values().flat_map(|subcommand| {
+            std::iter::once(subcommand.name.as_str())
+                .chain(subcommand.aliases.iter().map(String::as_str))
+        })
+    }
+
+    /// Walk down the subcommand tree following the arguments, returning the command to run and
+    /// the number of arguments used up in getting there
+    pub(crate) fn resolve(&mut self, args: &[&str]) -> (&mut Command<Context, E>, usize) {
+        let found = args.first().and_then(|arg| {
+            self.subcommands
+                .values()
+                .find(|subcommand| subcommand.is_called(arg))
+                .map(|subcommand| subcommand.name.clone())
+        });
+        match found {
+            Some(name) => {
+                let subcommand = self.subcommands.get_mut(&name).unwrap();
+                let (command, depth) = subcommand.resolve(&args[1..]);
+                (command, depth + 1)
+            }
+            None => (self, 0),
+        }
+    }
+
+    /// Complete the value of the named parameter with tab using the given function, which is
+    /// passed the context and the partial value typed so far, and returns the candidates. The
+    /// parameter has to have been added already.
+    pub fn with_completer<F>(mut self, parameter: &str, completer: F) -> Result<Command<Context, E>>
+    where
+        F: 'static + Fn(&Context, &str) -> Vec<String>,
+    {
+        if !self.parameters.iter().any(|param| param.name == parameter) {
+            return Err(Error::UnknownParameter(
+                self.name.clone(),
+                parameter.to_string(),
+            ));
+        }
+        self.completers
+            .insert(parameter.to_string(), Rc::new(completer));
+
+        Ok(self)
+    }
+
     /// Add a help summary for the command
     pub fn with_help(mut self, help: &str) -> Command<Context, E> {
         self.help_summary = Some(help.to_string());
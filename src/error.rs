@@ -14,12 +14,28 @@ pub enum Error {
     /// Parameter is defaulted when it's also required
     IllegalDefaultError(String),
 
+    /// Parameter is variadic when it can't be, either because it isn't the last positional
+    /// parameter or because its limits don't make sense
+    IllegalVariadicError(String),
+
     /// A required argument is missing
     MissingRequiredArgument(String, String),
 
     /// Too many arguments were provided
     TooManyArguments(String, usize),
 
+    /// An option was given which the command doesn't have
+    UnknownOption(String, String),
+
+    /// An option which takes a value was given without one
+    MissingOptionValue(String, String),
+
+    /// An argument doesn't match the type of its parameter
+    InvalidArgument(String, String, String),
+
+    /// Too few arguments were provided for a variadic parameter
+    TooFewArguments(String, String, usize),
+
     /// Error parsing a bool value
     ParseBoolError(std::str::ParseBoolError),
 
@@ -29,8 +45,40 @@ pub enum Error {
     /// Error parsing a float value
     ParseFloatError(num::ParseFloatError),
 
-    /// Command not found
-    UnknownCommand(String),
+    /// Error reading input, with the kind of error and its message
+    IoError(std::io::ErrorKind, String),
+
+    /// Command not found, with the names of any similar commands the user may have meant
+    UnknownCommand(String, Vec<String>),
+
+    /// A quote was opened at the given column but never closed
+    UnterminatedQuote(usize),
+
+    /// Command only groups subcommands, and none was given
+    MissingSubcommand(String),
+
+    /// Alias has the same name as a command, so it would hide it
+    IllegalAliasError(String),
+
+    /// A command's body was never ended with a line containing only the given terminator
+    UnterminatedBody(String),
+
+    /// Arguments to a command defined with clap were rejected by clap, with its message
+    ClapError(String),
+
+    /// Async command was run without awaiting it, with `execute` or `run` rather than
+    /// `execute_async` or `run_async`
+    AsyncCommand(String),
+
+    /// Async command was cancelled, with Ctrl-C or its cancellation token, before it was done
+    Cancelled,
+
+    /// `run_async` was called on tokio's current-thread runtime, where waiting for input would
+    /// block every other task
+    CurrentThreadRuntime,
+
+    /// A completer was added for a parameter the command doesn't have
+    UnknownParameter(String, String),
 }
 
 impl std::error::Error for Error {}
@@ -44,6 +92,9 @@ impl fmt::Display for Error {
             Error::IllegalRequiredError(parameter) => {
                 write!(f, "Error: Parameter '{}' cannot be required", parameter)
             }
+            Error::IllegalVariadicError(parameter) => {
+                write!(f, "Error: Parameter '{}' cannot be variadic", parameter)
+            }
             Error::MissingRequiredArgument(command, parameter) => write!(
                 f,
                 "Error: Missing required argument '{}' for command '{}'",
@@ -54,14 +105,97 @@ impl fmt::Display for Error {
                 "Error: Command '{}' can have no more than {} arguments",
                 command, nargs,
             ),
+            Error::UnknownOption(command, option) => write!(
+                f,
+                "Error: Unknown option '{}' for command '{}'",
+                option, command
+            ),
+            Error::MissingOptionValue(command, option) => write!(
+                f,
+                "Error: Option '{}' for command '{}' requires a value",
+                option, command
+            ),
+            Error::InvalidArgument(parameter, kind, value) => write!(
+                f,
+                "Error: Invalid value '{}' for argument '{}', expected {}",
+                value, parameter, kind
+            ),
+            Error::TooFewArguments(command, parameter, min) => write!(
+                f,
+                "Error: Argument '{}' for command '{}' needs at least {} values",
+                parameter, command, min
+            ),
             Error::ParseBoolError(error) => write!(f, "Error: {}", error,),
             Error::ParseFloatError(error) => write!(f, "Error: {}", error,),
             Error::ParseIntError(error) => write!(f, "Error: {}", error,),
-            Error::UnknownCommand(command) => write!(f, "Error: Unknown command '{}'", command),
+            Error::IoError(_, error) => write!(f, "Error: {}", error),
+            Error::UnknownCommand(command, suggestions) => {
+                write!(f, "Error: Unknown command '{}'", command)?;
+                if let Some((last, rest)) = suggestions.split_last() {
+                    let quoted = |name: &String| format!("'{}'", name);
+                    write!(f, ". Did you mean ")?;
+                    if !rest.is_empty() {
+                        let rest = rest.iter().map(quoted).collect::<Vec<String>>();
+                        write!(f, "{} or ", rest.join(", "))?;
+                    }
+                    write!(f, "{}?", quoted(last))?;
+                }
+                Ok(())
+            }
+            Error::UnterminatedQuote(column) => {
+                write!(f, "Error: Unterminated quote starting at column {}", column)
+            }
+            Error::MissingSubcommand(command) => {
+                write!(f, "Error: Command '{}' needs a subcommand", command)
+            }
+            Error::IllegalAliasError(alias) => {
+                write!(
+                    f,
+                    "Error: Alias '{}' would hide the command of the same name",
+                    alias
+                )
+            }
+            Error::UnterminatedBody(terminator) => {
+                write!(
+                    f,
+                    "Error: Body not ended by a line containing '{}'",
+                    terminator
+                )
+            }
+            Error::ClapError(error) => write!(f, "Error: {}", error),
+            Error::AsyncCommand(command) => write!(
+                f,
+                "Error: Command '{}' is async, and can only be run with run_async",
+                command
+            ),
+            Error::Cancelled => write!(f, "Error: Command cancelled"),
+            Error::CurrentThreadRuntime => write!(
+                f,
+                "Error: run_async needs tokio's multi-threaded runtime, not the current-thread one"
+            ),
+            Error::UnknownParameter(command, parameter) => write!(
+                f,
+                "Error: Command '{}' has no parameter '{}'",
+                command, parameter
+            ),
         }
     }
 }
 
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Self {
+        Error::IoError(error.kind(), error.to_string())
+    }
+}
+
+impl From<clap::Error> for Error {
+    fn from(error: clap::Error) -> Self {
+        let message = error.to_string();
+        let message = message.strip_prefix("error: ").unwrap_or(&message);
+        Error::ClapError(message.trim_end().to_string())
+    }
+}
+
 impl From<num::ParseIntError> for Error {
     fn from(error: num::ParseIntError) -> Self {
         Error::ParseIntError(error)
@@ -0,0 +1,40 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Handle which lets your commands ask the Repl to exit, the same as the built-in `exit` and
+/// `quit` commands do. Get one from [Repl::exit_handle](struct.Repl.html#method.exit_handle),
+/// and move it into the closure for your command:
+/// ```
+/// use repl_rs::{Command, Repl, Result, Value};
+/// use std::collections::HashMap;
+///
+/// let repl = Repl::<(), repl_rs::Error>::new(());
+/// let exit = repl.exit_handle();
+/// let repl = repl.add_command(Command::new(
+///     "logout",
+///     move |_args: HashMap<String, Value>, _context: &mut ()| -> Result<Option<String>> {
+///         exit.request_exit();
+///         Ok(Some("Bye!".to_string()))
+///     },
+/// ));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct ExitHandle {
+    requested: Arc<AtomicBool>,
+}
+
+impl ExitHandle {
+    /// Ask the Repl to exit once the current command is done
+    pub fn request_exit(&self) {
+        self.requested.store(true, Ordering::SeqCst);
+    }
+
+    /// Whether the Repl has been asked to exit
+    pub fn is_exit_requested(&self) -> bool {
+        self.requested.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn reset(&self) {
+        self.requested.store(false, Ordering::SeqCst);
+    }
+}
@@ -1,7 +1,69 @@
 use crate::error::*;
-use crate::Parameter;
+use crate::{Command, Parameter, ParameterType};
+use std::io::Write;
 use yansi::Paint;
 
+/// Help for one of a command's parameters, part of its [HelpEntry](struct.HelpEntry.html)
+#[derive(Debug)]
+pub struct ParameterHelp {
+    /// Parameter name
+    pub name: String,
+
+    /// The parameter as it's written in a usage line, for instance `name...` or
+    /// `-c|--count <count>`
+    pub usage: String,
+
+    /// Whether the parameter is required
+    pub required: bool,
+
+    /// Type of value the parameter takes
+    pub kind: ParameterType,
+
+    /// Default value, if the parameter has one
+    pub default: Option<String>,
+
+    /// Values the parameter is restricted to, if any
+    pub choices: Vec<String>,
+
+    /// Description of the parameter
+    pub help: Option<String>,
+}
+
+impl ParameterHelp {
+    pub(crate) fn new(parameter: &Parameter) -> Self {
+        Self {
+            name: parameter.name.clone(),
+            usage: parameter.usage(),
+            required: parameter.required,
+            kind: parameter.kind.clone(),
+            default: parameter.default.clone(),
+            choices: parameter.choices.clone(),
+            help: parameter.help.clone(),
+        }
+    }
+
+    // The description, followed by the type, default and allowed values, as shown in the
+    // argument table
+    fn description(&self) -> String {
+        let mut details = vec![];
+        match self.kind {
+            _ if !self.choices.is_empty() => {
+                details.push(format!("one of: {}", self.choices.join(", ")))
+            }
+            ParameterType::String | ParameterType::Bool => (),
+            ref kind => details.push(kind.to_string()),
+        }
+        if let Some(default) = &self.default {
+            details.push(format!("default: {}", default));
+        }
+        match (&self.help, details.is_empty()) {
+            (Some(help), true) => help.clone(),
+            (Some(help), false) => format!("{} ({})", help, details.join("; ")),
+            (None, _) => details.join("; "),
+        }
+    }
+}
+
 /// Help entry which gets sent to [HelpViewer](trait.HelpViewer.html) when help for a particular
 /// command is requested
 #[derive(Debug)]
@@ -9,26 +71,35 @@ pub struct HelpEntry {
     /// Command from `help <command>`
     pub command: String,
 
+    /// Other names the command can be run as
+    pub aliases: Vec<String>,
+
     /// Parameters defined for the command
-    pub parameters: Vec<(String, bool)>,
+    pub parameters: Vec<ParameterHelp>,
 
     /// Help summary for the command
     pub summary: Option<String>,
+
+    /// Help entries for the command's subcommands, if it has any
+    pub subcommands: Vec<HelpEntry>,
 }
 
 impl HelpEntry {
-    pub(crate) fn new(
-        command_name: &str,
-        parameters: &[Parameter],
-        summary: &Option<String>,
-    ) -> Self {
+    pub(crate) fn new<Context, E>(command_path: &str, command: &Command<Context, E>) -> Self {
+        let mut subcommands = command
+            .subcommands
+            .values()
+            .map(|subcommand| {
+                HelpEntry::new(&format!("{} {}", command_path, subcommand.name), subcommand)
+            })
+            .collect::<Vec<HelpEntry>>();
+        subcommands.sort_by_key(|d| d.command.clone());
         Self {
-            command: command_name.to_string(),
-            parameters: parameters
-                .iter()
-                .map(|pd| (pd.name.clone(), pd.required))
-                .collect(),
-            summary: summary.clone(),
+            command: command_path.to_string(),
+            aliases: command.aliases.clone(),
+            parameters: command.parameters.iter().map(ParameterHelp::new).collect(),
+            summary: command.help_summary.clone(),
+            subcommands,
         }
     }
 }
@@ -46,6 +117,9 @@ pub struct HelpContext {
 
     /// List of help entries
     pub help_entries: Vec<HelpEntry>,
+
+    /// Aliases defined with the built-in `alias` command, and what they expand to
+    pub aliases: Vec<(String, String)>,
 }
 
 impl HelpContext {
@@ -54,24 +128,28 @@ impl HelpContext {
         app_version: &str,
         app_purpose: &str,
         help_entries: Vec<HelpEntry>,
+        aliases: Vec<(String, String)>,
     ) -> Self {
         Self {
             app_name: app_name.into(),
             app_version: app_version.into(),
             app_purpose: app_purpose.into(),
             help_entries,
+            aliases,
         }
     }
 }
 
 /// Trait to be used if you want your own custom Help output
 pub trait HelpViewer {
-    /// Called when the plain `help` command is called with no arguments
-    fn help_general(&self, context: &HelpContext) -> Result<()>;
+    /// Called when the plain `help` command is called with no arguments. The help should be
+    /// written to `output`, and is then printed to the Repl's output sink, or returned from
+    /// [Repl::execute](struct.Repl.html#method.execute)
+    fn help_general(&self, context: &HelpContext, output: &mut dyn Write) -> Result<()>;
 
     /// Called when the `help` command is called with a command argument (i.e., `help foo`).
     /// Note that you won't have to handle an unknown command - it'll be handled in the caller
-    fn help_command(&self, entry: &HelpEntry) -> Result<()>;
+    fn help_command(&self, entry: &HelpEntry, output: &mut dyn Write) -> Result<()>;
 }
 
 /// Default [HelpViewer](trait.HelpViewer.html)
@@ -84,48 +162,181 @@ impl DefaultHelpViewer {
 }
 
 impl HelpViewer for DefaultHelpViewer {
-    fn help_general(&self, context: &HelpContext) -> Result<()> {
-        self.print_help_header(context);
+    fn help_general(&self, context: &HelpContext, output: &mut dyn Write) -> Result<()> {
+        self.print_help_header(context, output)?;
         for entry in &context.help_entries {
-            print!("{}", entry.command);
-            if entry.summary.is_some() {
-                print!(" - {}", entry.summary.as_ref().unwrap());
+            write!(output, "{}", entry.command)?;
+            if !entry.aliases.is_empty() {
+                write!(output, " ({})", entry.aliases.join(", "))?;
+            }
+            if let Some(summary) = &entry.summary {
+                write!(output, " - {}", summary)?;
+            }
+            writeln!(output)?;
+        }
+        if !context.aliases.is_empty() {
+            writeln!(output, "Aliases:")?;
+            for (alias, expansion) in &context.aliases {
+                writeln!(output, "\t{} = {}", alias, expansion)?;
             }
-            println!();
         }
 
         Ok(())
     }
 
-    fn help_command(&self, entry: &HelpEntry) -> Result<()> {
-        if entry.summary.is_some() {
-            println!("{}: {}", entry.command, entry.summary.as_ref().unwrap());
+    fn help_command(&self, entry: &HelpEntry, output: &mut dyn Write) -> Result<()> {
+        if let Some(summary) = &entry.summary {
+            writeln!(output, "{}: {}", entry.command, summary)?;
         } else {
-            println!("{}:", entry.command);
+            writeln!(output, "{}:", entry.command)?;
+        }
+        if !entry.aliases.is_empty() {
+            writeln!(output, "Aliases: {}", entry.aliases.join(", "))?;
+        }
+        writeln!(output, "Usage:")?;
+        if entry.subcommands.is_empty() || !entry.parameters.is_empty() {
+            write!(output, "\t{}", entry.command)?;
+            for param in &entry.parameters {
+                if param.required {
+                    write!(output, " {}", param.usage)?;
+                } else {
+                    write!(output, " [{}]", param.usage)?;
+                }
+            }
+            writeln!(output)?;
+        }
+        if !entry.subcommands.is_empty() {
+            writeln!(output, "\t{} <subcommand>", entry.command)?;
+        }
+        if !entry.parameters.is_empty() {
+            writeln!(output, "Arguments:")?;
+            let width = entry
+                .parameters
+                .iter()
+                .map(|param| param.usage.chars().count())
+                .max()
+                .unwrap_or(0);
+            for param in &entry.parameters {
+                let description = param.description();
+                if description.is_empty() {
+                    writeln!(output, "\t{}", param.usage)?;
+                } else {
+                    writeln!(
+                        output,
+                        "\t{:width$}  {}",
+                        param.usage,
+                        description,
+                        width = width
+                    )?;
+                }
+            }
         }
-        println!("Usage:");
-        print!("\t{}", entry.command);
-        for param in &entry.parameters {
-            if param.1 {
-                print!(" {}", param.0);
-            } else {
-                print!(" [{}]", param.0);
+        if !entry.subcommands.is_empty() {
+            writeln!(output, "Subcommands:")?;
+            for subcommand in &entry.subcommands {
+                let mut name = subcommand.command.rsplit(' ').next().unwrap().to_string();
+                if !subcommand.aliases.is_empty() {
+                    name = format!("{} ({})", name, subcommand.aliases.join(", "));
+                }
+                match &subcommand.summary {
+                    Some(summary) => writeln!(output, "\t{} - {}", name, summary)?,
+                    None => writeln!(output, "\t{}", name)?,
+                }
             }
         }
-        println!();
 
         Ok(())
     }
 }
 
 impl DefaultHelpViewer {
-    fn print_help_header(&self, context: &HelpContext) {
+    fn print_help_header(&self, context: &HelpContext, output: &mut dyn Write) -> Result<()> {
         let header = format!(
             "{} {}: {}",
             context.app_name, context.app_version, context.app_purpose
         );
         let underline = Paint::new(" ".repeat(header.len())).strikethrough();
-        println!("{}", header);
-        println!("{}", underline);
+        writeln!(output, "{}", header)?;
+        writeln!(output, "{}", underline)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::*;
+    use crate::help::{DefaultHelpViewer, HelpEntry, HelpViewer};
+    use crate::{Command, Parameter, ParameterType, Value};
+    use std::collections::HashMap;
+
+    fn foo(_args: HashMap<String, Value>, _context: &mut ()) -> Result<Option<String>> {
+        Ok(None)
+    }
+
+    #[test]
+    fn test_argument_table() -> Result<()> {
+        let command = Command::new("grant", foo)
+            .with_parameter(
+                Parameter::new("user")
+                    .set_required(true)?
+                    .with_help("User to grant the role to"),
+            )?
+            .with_parameter(
+                Parameter::new("role")
+                    .set_choices(&["admin", "reader"])?
+                    .set_default("reader")?
+                    .with_help("Role to grant"),
+            )?
+            .with_parameter(
+                Parameter::new("days")
+                    .set_type(ParameterType::Int)?
+                    .set_short('d')
+                    .set_long("days"),
+            )?
+            .with_parameter(Parameter::new("notify").set_flag(true)?)?
+            .with_help("Grant a role");
+        let mut output = vec![];
+        DefaultHelpViewer::new().help_command(&HelpEntry::new("grant", &command), &mut output)?;
+
+        assert_eq!(
+            "grant: Grant a role\n\
+             Usage:\n\
+             \tgrant user [role] [-d|--days <days>] [--notify]\n\
+             Arguments:\n\
+             \tuser              User to grant the role to\n\
+             \trole              Role to grant (one of: admin, reader; default: reader)\n\
+             \t-d|--days <days>  int\n\
+             \t--notify\n",
+            String::from_utf8(output).unwrap()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parameters_and_subcommands() -> Result<()> {
+        let command = Command::new("user", foo)
+            .with_parameter(Parameter::new("name").with_help("User to show"))?
+            .with_subcommand(Command::new("add", foo).with_help("Add a user"))
+            .with_subcommand(Command::new("remove", foo).with_alias("rm"))
+            .with_help("Show or change users");
+        let mut output = vec![];
+        DefaultHelpViewer::new().help_command(&HelpEntry::new("user", &command), &mut output)?;
+
+        assert_eq!(
+            "user: Show or change users\n\
+             Usage:\n\
+             \tuser [name]\n\
+             \tuser <subcommand>\n\
+             Arguments:\n\
+             \tname  User to show\n\
+             Subcommands:\n\
+             \tadd - Add a user\n\
+             \tremove (rm)\n",
+            String::from_utf8(output).unwrap()
+        );
+
+        Ok(())
     }
 }
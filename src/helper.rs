@@ -0,0 +1,891 @@
+use crate::alias::Aliases;
+use crate::command::Completer;
+use crate::input;
+use crate::lexer;
+use crate::{Command, Parameter, ParameterType};
+use rustyline::completion::{self, FilenameCompleter};
+use rustyline::highlight::Highlighter;
+use rustyline::hint::{Hint, Hinter};
+use rustyline::validate::{ValidationContext, ValidationResult, Validator};
+use rustyline_derive::Helper;
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ops::Range;
+use yansi::Paint;
+
+/// How command names are matched against what's been typed when completing with tab
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MatchStrategy {
+    /// Names which start with what's been typed (the default)
+    Prefix,
+
+    /// Names which start with what's been typed, ignoring case
+    CaseInsensitivePrefix,
+
+    /// Names which contain the characters typed in the same order, though not necessarily next
+    /// to each other, so `usrad` matches `user-add`. Better matches are listed first.
+    Fuzzy,
+}
+
+impl MatchStrategy {
+    // Score how well the name matches what's been typed, higher being better, or None if it
+    // doesn't match at all
+    fn score(&self, name: &str, partial: &str) -> Option<i64> {
+        // Shorter names are better matches, all else being equal
+        let length_penalty = name.chars().count() as i64;
+        match self {
+            MatchStrategy::Prefix => name.starts_with(partial).then(|| -length_penalty),
+            MatchStrategy::CaseInsensitivePrefix => name
+                .to_lowercase()
+                .starts_with(&partial.to_lowercase())
+                .then(|| -length_penalty),
+            MatchStrategy::Fuzzy => fuzzy_score(name, partial).map(|score| score - length_penalty),
+        }
+    }
+}
+
+// Score a case-insensitive subsequence match, rewarding matches at the start of the name or of
+// a word within it, and runs of consecutive matching characters
+fn fuzzy_score(name: &str, partial: &str) -> Option<i64> {
+    let mut score = 0;
+    let mut wanted = partial.chars().flat_map(char::to_lowercase).peekable();
+    let mut previous: Option<char> = None;
+    let mut previous_matched = false;
+    for c in name.chars() {
+        let matched = match wanted.peek() {
+            Some(w) if c.to_lowercase().eq(std::iter::once(*w)) => {
+                wanted.next();
+                score += 10;
+                match previous {
+                    None => score += 30,
+                    Some(p) if !p.is_alphanumeric() => score += 20,
+                    _ => (),
+                }
+                if previous_matched {
+                    score += 15;
+                }
+                true
+            }
+            _ => false,
+        };
+        previous = Some(c);
+        previous_matched = matched;
+    }
+
+    wanted.peek().is_none().then_some(score)
+}
+
+// Command name, and what's needed to complete its subcommands and arguments
+pub(crate) struct CompletionNode<Context> {
+    name: String,
+    aliases: Vec<String>,
+    body: bool,
+    subcommands: Vec<CompletionNode<Context>>,
+    parameters: Vec<Parameter>,
+    completers: HashMap<String, Completer<Context>>,
+}
+
+impl<Context> CompletionNode<Context> {
+    pub(crate) fn new<E>(command: &Command<Context, E>) -> Self {
+        Self {
+            name: command.name.clone(),
+            aliases: command.aliases.clone(),
+            body: command.body.is_some(),
+            subcommands: command
+                .subcommands
+                .values()
+                .map(CompletionNode::new)
+                .collect(),
+            parameters: command.parameters.clone(),
+            completers: command.completers.clone(),
+        }
+    }
+
+    // Node for one of the Repl's built-in commands, which have no parameters
+    pub(crate) fn builtin(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            aliases: vec![],
+            body: false,
+            subcommands: vec![],
+            parameters: vec![],
+            completers: HashMap::new(),
+        }
+    }
+
+    fn is_called(&self, name: &str) -> bool {
+        self.name == name || self.aliases.iter().any(|alias| alias == name)
+    }
+
+    // Find the named option that an argument refers to, if it is one
+    fn option(&self, arg: &str) -> Option<&Parameter> {
+        if let Some(long) = arg.strip_prefix("--") {
+            let name = long.split('=').next().unwrap();
+            self.parameters
+                .iter()
+                .find(|parameter| parameter.long.as_deref() == Some(name))
+        } else if arg.len() > 1 && arg.starts_with('-') && arg.parse::<f64>().is_err() {
+            let short = arg.chars().last();
+            self.parameters
+                .iter()
+                .find(|parameter| parameter.short.is_some() && parameter.short == short)
+        } else {
+            None
+        }
+    }
+
+    // Complete an argument to this command, given the arguments before it
+    fn complete_argument(
+        &self,
+        args: &[String],
+        partial: &str,
+        context: Option<&Context>,
+    ) -> Vec<String> {
+        if let Some(parameter) = args.last().and_then(|last| {
+            self.option(last)
+                .filter(|parameter| !parameter.flag && !last.contains('='))
+        }) {
+            return self.complete_value(parameter, partial, context);
+        }
+        if partial.starts_with('-') && partial.parse::<f64>().is_err() {
+            return self
+                .parameters
+                .iter()
+                .filter_map(|parameter| parameter.long.as_ref())
+                .map(|long| format!("--{}", long))
+                .filter(|option| option.starts_with(partial))
+                .collect();
+        }
+
+        let index = self.positional_count(args);
+        let positionals = self.positionals();
+        match positionals.get(index).or_else(|| {
+            positionals
+                .last()
+                .filter(|parameter| parameter.variadic.is_some())
+        }) {
+            Some(parameter) => self.complete_value(parameter, partial, context),
+            None => vec![],
+        }
+    }
+
+    fn positionals(&self) -> Vec<&Parameter> {
+        self.parameters
+            .iter()
+            .filter(|parameter| parameter.is_positional())
+            .collect()
+    }
+
+    // Work out how many positional arguments have been given
+    fn positional_count(&self, args: &[String]) -> usize {
+        let mut count = 0;
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            if arg == "--" {
+                count += args.count();
+                break;
+            }
+            match self.option(arg) {
+                Some(parameter) => {
+                    if !parameter.flag && !arg.contains('=') {
+                        args.next();
+                    }
+                }
+                None => count += 1,
+            }
+        }
+
+        count
+    }
+
+    // The parameters still to be given after the given arguments, as they'd be written in a
+    // usage line, with the optional ones in brackets
+    fn remaining_parameters(&self, args: &[String]) -> Vec<String> {
+        let mut remaining = vec![];
+        if args.is_empty() && !self.subcommands.is_empty() {
+            remaining.push("<subcommand>".to_string());
+        }
+        let given = self.positional_count(args);
+        let positionals = self.positionals();
+        for (index, parameter) in positionals.iter().enumerate() {
+            if index >= given || parameter.variadic.is_some() && index + 1 == positionals.len() {
+                remaining.push(signature(parameter, index < given));
+            }
+        }
+        for parameter in &self.parameters {
+            let used = args.iter().any(|arg| {
+                self.option(arg)
+                    .is_some_and(|option| option.name == parameter.name)
+            });
+            if !parameter.is_positional() && (parameter.multiple || !used) {
+                remaining.push(signature(parameter, used));
+            }
+        }
+
+        remaining
+    }
+
+    fn complete_value(
+        &self,
+        parameter: &Parameter,
+        partial: &str,
+        context: Option<&Context>,
+    ) -> Vec<String> {
+        if let Some(completer) = self.completers.get(&parameter.name) {
+            return context.map_or_else(Vec::new, |context| completer(context, partial));
+        }
+        let choices = match parameter.kind {
+            _ if !parameter.choices.is_empty() => parameter.choices.clone(),
+            ParameterType::Bool => vec!["true".to_string(), "false".to_string()],
+            ParameterType::Path => return complete_path(partial),
+            _ => vec![],
+        };
+        choices
+            .into_iter()
+            .filter(|choice| choice.starts_with(partial))
+            .collect()
+    }
+}
+
+// How a parameter is shown in a hint, in brackets if it's optional or has already been given
+fn signature(parameter: &Parameter, given: bool) -> String {
+    if parameter.required && !given {
+        parameter.usage()
+    } else {
+        format!("[{}]", parameter.usage())
+    }
+}
+
+// Hint for the rest of a command's parameters, which is only shown, never inserted into the line
+pub(crate) struct ParameterHint(String);
+
+impl Hint for ParameterHint {
+    fn display(&self) -> &str {
+        &self.0
+    }
+
+    fn completion(&self) -> Option<&str> {
+        None
+    }
+}
+
+// Where the words typed so far lead in the command tree
+struct Position<'a, Context> {
+    // Command named by the words, if they name one yet
+    command: Option<&'a CompletionNode<Context>>,
+
+    // Commands which the next word could name
+    candidates: &'a [CompletionNode<Context>],
+
+    // Arguments given to the command
+    args: Vec<String>,
+
+    // Number of words naming the command and its subcommands
+    depth: usize,
+}
+
+// Complete a file path, returning the whole of the completed argument
+fn complete_path(partial: &str) -> Vec<String> {
+    match FilenameCompleter::new().complete_path(partial, partial.len()) {
+        Ok((start, pairs)) => pairs
+            .into_iter()
+            .map(|pair| format!("{}{}", &partial[..start], pair.replacement))
+            .collect(),
+        Err(_) => vec![],
+    }
+}
+
+// rustyline Helper struct
+// Does command and argument completion with <tab>, hints for the rest of the command's
+// parameters and highlighting of the line, if they're turned on for the REPL. It also keeps
+// reading lines while the command continues onto the next one.
+#[derive(Helper)]
+pub(crate) struct Helper<Context> {
+    commands: Vec<CompletionNode<Context>>,
+    aliases: Aliases,
+    strategy: MatchStrategy,
+    // Context passed to the completers, which the Repl lends the helper while a line is being
+    // read; see readline
+    context: RefCell<Option<Context>>,
+    pub(crate) completion: bool,
+    pub(crate) hints: bool,
+    pub(crate) highlighting: bool,
+}
+
+// Read a line with the editor, lending the context to the helper while it's being read so the
+// completers can see it, and taking it back afterwards for the commands to change
+pub(crate) fn readline<Context>(
+    editor: &mut rustyline::Editor<Helper<Context>>,
+    prompt: &str,
+    context: &mut Option<Context>,
+) -> rustyline::Result<String> {
+    if let Some(helper) = editor.helper() {
+        *helper.context.borrow_mut() = context.take();
+    }
+    let read = editor.readline(prompt);
+    if let Some(helper) = editor.helper() {
+        *context = helper.context.borrow_mut().take();
+    }
+
+    read
+}
+
+impl<Context> Helper<Context> {
+    pub(crate) fn new(strategy: MatchStrategy, aliases: Aliases) -> Self {
+        Self {
+            commands: vec![],
+            aliases,
+            strategy,
+            context: RefCell::new(None),
+            completion: false,
+            hints: false,
+            highlighting: false,
+        }
+    }
+
+    pub(crate) fn add_command(&mut self, command: CompletionNode<Context>) {
+        self.commands.push(command);
+    }
+
+    // Complete the word under the cursor. The first word is completed from the command names
+    // and aliases, descending into subcommands for each complete word before it, and the rest
+    // from the parameters of the command. Returns the start of the word being completed, and
+    // the candidates
+    pub(crate) fn complete_line(&self, line: &str, pos: usize) -> (usize, Vec<String>) {
+        let line = &line[..pos];
+        let start = line
+            .char_indices()
+            .rev()
+            .find(|(_, c)| c.is_whitespace())
+            .map(|(index, c)| index + c.len_utf8())
+            .unwrap_or(0);
+        let partial = &line[start..];
+        let Position {
+            command,
+            candidates,
+            args,
+            ..
+        } = match lexer::tokenize(&line[..start]).map(|words| self.walk(words)) {
+            Ok(Some(position)) => position,
+            _ => return (start, vec![]),
+        };
+
+        // Complete command names using the match strategy, best matches first
+        let mut ret: Vec<String> = vec![];
+        if args.is_empty() {
+            let mut names = candidates
+                .iter()
+                .flat_map(|node| std::iter::once(&node.name).chain(&node.aliases))
+                .cloned()
+                .collect::<Vec<String>>();
+            if command.is_none() {
+                names.extend(self.aliases.names());
+            }
+            let mut matches = names
+                .into_iter()
+                .filter_map(|name| {
+                    self.strategy
+                        .score(&name, partial)
+                        .map(|score| (score, name))
+                })
+                .collect::<Vec<(i64, String)>>();
+            matches.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+            ret.extend(matches.into_iter().map(|(_, name)| name));
+        }
+        if let Some(command) = command {
+            let context = self.context.borrow();
+            ret.extend(command.complete_argument(&args, partial, context.as_ref()));
+        }
+        (start, ret)
+    }
+
+    // Follow the words down the command tree, after expanding any alias. Returns None if the
+    // first word isn't a command.
+    fn walk(&self, words: Vec<String>) -> Option<Position<'_, Context>> {
+        let mut position = Position {
+            command: None,
+            candidates: &self.commands,
+            args: vec![],
+            depth: 0,
+        };
+        for word in self.aliases.expand(words) {
+            match position
+                .candidates
+                .iter()
+                .find(|node| node.is_called(&word))
+            {
+                Some(node) if position.args.is_empty() => {
+                    position.command = Some(node);
+                    position.candidates = &node.subcommands;
+                    position.depth += 1;
+                }
+                _ if position.command.is_none() => return None,
+                _ => position.args.push(word),
+            }
+        }
+
+        Some(position)
+    }
+
+    // Whether the command named by the words takes a body after them
+    fn takes_body(&self, words: &[String]) -> bool {
+        self.walk(words.to_vec())
+            .and_then(|position| position.command)
+            .is_some_and(|command| command.body)
+    }
+
+    // Hint at the parameters of the command which are still to be given, when the cursor is at
+    // the end of the line
+    pub(crate) fn hint_line(&self, line: &str, pos: usize) -> Option<String> {
+        if pos < line.len() {
+            return None;
+        }
+        let position = self.walk(lexer::tokenize(line).ok()?)?;
+        let remaining = position.command?.remaining_parameters(&position.args);
+        if remaining.is_empty() {
+            return None;
+        }
+        let separator = if line.ends_with(char::is_whitespace) {
+            ""
+        } else {
+            " "
+        };
+
+        Some(format!("{}{}", separator, remaining.join(" ")))
+    }
+
+    // Color the words naming a command green, or the first word red if it isn't a command,
+    // and any quoted strings in the arguments yellow
+    pub(crate) fn highlight_line(&self, line: &str) -> String {
+        let spans = word_spans(line);
+        let words = spans
+            .iter()
+            .map(|span| {
+                let word = &line[span.clone()];
+                match lexer::tokenize(word) {
+                    Ok(mut tokens) if tokens.len() == 1 => tokens.remove(0),
+                    _ => word.to_string(),
+                }
+            })
+            .collect::<Vec<String>>();
+        // An alias stands in for all of the words it expands to
+        let expanded = words
+            .first()
+            .and_then(|first| self.aliases.get(first))
+            .map_or(1, |expansion| expansion.len());
+        let (known, depth) = match self.walk(words) {
+            Some(position) if expanded > 1 => (true, 1 + position.depth.saturating_sub(expanded)),
+            Some(position) => (true, position.depth),
+            None => (false, 1),
+        };
+
+        let mut highlighted = String::new();
+        let mut end = 0;
+        for (index, span) in spans.into_iter().enumerate() {
+            highlighted.push_str(&line[end..span.start]);
+            let word = &line[span.clone()];
+            if index < depth && known {
+                highlighted.push_str(&Paint::green(word).to_string());
+            } else if index < depth {
+                highlighted.push_str(&Paint::red(word).to_string());
+            } else {
+                highlighted.push_str(&highlight_quotes(word));
+            }
+            end = span.end;
+        }
+        highlighted.push_str(&line[end..]);
+
+        highlighted
+    }
+}
+
+// Find where each word of the line starts and ends, including any quotes, by the same rules as
+// the lexer. A quote which hasn't been closed yet runs to the end of the line.
+fn word_spans(line: &str) -> Vec<Range<usize>> {
+    let mut spans = vec![];
+    let mut start: Option<usize> = None;
+    let mut chars = line.char_indices();
+    while let Some((index, c)) = chars.next() {
+        if c.is_whitespace() {
+            if let Some(start) = start.take() {
+                spans.push(start..index);
+            }
+            continue;
+        }
+        start.get_or_insert(index);
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '\'' => while !matches!(chars.next(), Some((_, '\'')) | None) {},
+            '"' => loop {
+                match chars.next() {
+                    Some((_, '\\')) => {
+                        chars.next();
+                    }
+                    Some((_, '"')) | None => break,
+                    _ => (),
+                }
+            },
+            _ => (),
+        }
+    }
+    if let Some(start) = start {
+        spans.push(start..line.len());
+    }
+
+    spans
+}
+
+// Color the quoted parts of a word
+fn highlight_quotes(word: &str) -> String {
+    let mut highlighted = String::new();
+    let mut chars = word.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        match c {
+            '\\' => {
+                highlighted.push(c);
+                if let Some((_, escaped)) = chars.next() {
+                    highlighted.push(escaped);
+                }
+            }
+            '\'' | '"' => {
+                let mut escaped = false;
+                let end = loop {
+                    match chars.next() {
+                        Some((index, next)) if next == c && !escaped => break index + 1,
+                        Some((_, next)) => escaped = c == '"' && next == '\\' && !escaped,
+                        None => break word.len(),
+                    }
+                };
+                highlighted.push_str(&Paint::yellow(&word[start..end]).to_string());
+            }
+            _ => highlighted.push(c),
+        }
+    }
+
+    highlighted
+}
+
+impl<Context> completion::Completer for Helper<Context> {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &rustyline::Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Self::Candidate>)> {
+        if !self.completion {
+            return Ok((pos, vec![]));
+        }
+        Ok(self.complete_line(line, pos))
+    }
+}
+
+impl<Context> Validator for Helper<Context> {
+    fn validate(&self, ctx: &mut ValidationContext) -> rustyline::Result<ValidationResult> {
+        if input::is_complete(ctx.input(), |words| self.takes_body(words)) {
+            Ok(ValidationResult::Valid(None))
+        } else {
+            Ok(ValidationResult::Incomplete)
+        }
+    }
+}
+
+impl<Context> Hinter for Helper<Context> {
+    type Hint = ParameterHint;
+
+    fn hint(&self, line: &str, pos: usize, _ctx: &rustyline::Context<'_>) -> Option<Self::Hint> {
+        if !self.hints {
+            return None;
+        }
+        self.hint_line(line, pos).map(ParameterHint)
+    }
+}
+
+impl<Context> Highlighter for Helper<Context> {
+    fn highlight<'l>(&self, line: &'l str, _pos: usize) -> Cow<'l, str> {
+        if !self.highlighting {
+            return Cow::Borrowed(line);
+        }
+        Cow::Owned(self.highlight_line(line))
+    }
+
+    fn highlight_hint<'h>(&self, hint: &'h str) -> Cow<'h, str> {
+        Cow::Owned(Paint::new(hint).dimmed().to_string())
+    }
+
+    fn highlight_char(&self, _line: &str, _pos: usize) -> bool {
+        // Redraw the line on every key, since what a word is colored changes as it's typed
+        self.highlighting
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::alias::Aliases;
+    use crate::error::*;
+    use crate::helper::{CompletionNode, Helper, MatchStrategy};
+    use crate::{Command, Parameter, ParameterType, Value};
+    use std::collections::HashMap;
+    use yansi::Paint;
+
+    fn foo<T>(_args: HashMap<String, Value>, _context: &mut T) -> Result<Option<String>> {
+        Ok(None)
+    }
+
+    fn helper(commands: Vec<Command<Vec<String>, Error>>) -> Helper<Vec<String>> {
+        helper_matching(MatchStrategy::Prefix, commands)
+    }
+
+    fn helper_matching(
+        strategy: MatchStrategy,
+        commands: Vec<Command<Vec<String>, Error>>,
+    ) -> Helper<Vec<String>> {
+        let mut helper = Helper::new(strategy, Aliases::default());
+        for command in &commands {
+            helper.add_command(CompletionNode::new(command));
+        }
+        *helper.context.borrow_mut() = Some(vec!["alice".to_string(), "bob".to_string()]);
+        helper
+    }
+
+    #[test]
+    fn test_completion_descends_into_subcommands() -> Result<()> {
+        let helper = helper(vec![
+            Command::group("user")
+                .with_subcommand(
+                    Command::new("add", foo)
+                        .with_parameter(Parameter::new("name").set_required(true)?)?,
+                )
+                .with_subcommand(Command::new("list", foo))
+                .with_subcommand(
+                    Command::group("role").with_subcommand(Command::new("grant", foo)),
+                ),
+            Command::new("foo", foo),
+        ]);
+
+        assert_eq!((0, vec!["user".to_string()]), helper.complete_line("us", 2));
+        let (start, mut candidates) = helper.complete_line("user ", 5);
+        candidates.sort();
+        assert_eq!(5, start);
+        assert_eq!(vec!["add", "list", "role"], candidates);
+        assert_eq!(
+            (10, vec!["grant".to_string()]),
+            helper.complete_line("user role gr", 12)
+        );
+        assert_eq!((4, vec![]), helper.complete_line("foo ba", 6));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_match_strategies() {
+        let commands = || {
+            vec![
+                Command::new("List", foo),
+                Command::new("list-users", foo),
+                Command::new("blast", foo),
+                Command::new("user-list", foo),
+            ]
+        };
+
+        let helper = helper_matching(MatchStrategy::Prefix, commands());
+        assert_eq!(
+            (0, vec!["list-users".to_string()]),
+            helper.complete_line("li", 2)
+        );
+
+        let helper = helper_matching(MatchStrategy::CaseInsensitivePrefix, commands());
+        assert_eq!(
+            (0, vec!["List".to_string(), "list-users".to_string()]),
+            helper.complete_line("li", 2)
+        );
+
+        let helper = helper_matching(MatchStrategy::Fuzzy, commands());
+        assert_eq!(
+            (
+                0,
+                vec![
+                    "List".to_string(),
+                    "list-users".to_string(),
+                    "user-list".to_string(),
+                    "blast".to_string()
+                ]
+            ),
+            helper.complete_line("lst", 3)
+        );
+        assert_eq!(
+            (0, vec!["user-list".to_string()]),
+            helper.complete_line("ulis", 4)
+        );
+    }
+
+    #[test]
+    fn test_alias_completion() -> Result<()> {
+        let aliases = Aliases::default();
+        let mut helper: Helper<Vec<String>> = Helper::new(MatchStrategy::Prefix, aliases.clone());
+        let command: Command<Vec<String>, Error> = Command::new("list", foo)
+            .with_alias("ls")
+            .with_parameter(Parameter::new("sort").set_choices(&["name", "size"])?)?;
+        helper.add_command(CompletionNode::new(&command));
+        aliases.insert("lsn", vec!["list".into(), "name".into()]);
+        aliases.insert("lz", vec!["list".into()]);
+
+        assert_eq!(
+            (0, vec!["ls".to_string(), "lsn".to_string()]),
+            helper.complete_line("ls", 2)
+        );
+        assert_eq!(
+            (3, vec!["size".to_string()]),
+            helper.complete_line("ls s", 4)
+        );
+        assert_eq!(
+            (3, vec!["size".to_string()]),
+            helper.complete_line("lz s", 4)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parameter_completion() -> Result<()> {
+        let helper = helper(vec![Command::new("grant", foo)
+            .with_parameter(Parameter::new("user").set_required(true)?)?
+            .with_parameter(
+                Parameter::new("role")
+                    .set_required(true)?
+                    .set_choices(&["admin", "author", "reader"])?,
+            )?
+            .with_parameter(
+                Parameter::new("notify")
+                    .set_long("notify")
+                    .set_type(ParameterType::Bool)?,
+            )?
+            .with_parameter(Parameter::new("verbose").set_flag(true)?.set_short('v'))?
+            .with_completer("user", |users: &Vec<String>, partial| {
+                users
+                    .iter()
+                    .filter(|user| user.starts_with(partial))
+                    .cloned()
+                    .collect()
+            })?]);
+
+        assert_eq!(
+            (6, vec!["alice".to_string(), "bob".to_string()]),
+            helper.complete_line("grant ", 6)
+        );
+        assert_eq!(
+            (12, vec!["admin".to_string(), "author".to_string()]),
+            helper.complete_line("grant alice a", 13)
+        );
+        assert_eq!(
+            (15, vec!["admin".to_string()]),
+            helper.complete_line("grant -v alice ad rest", 17)
+        );
+        assert_eq!(
+            (19, vec!["true".to_string()]),
+            helper.complete_line("grant bob --notify t", 20)
+        );
+        assert_eq!(
+            (6, vec!["--notify".to_string(), "--verbose".to_string()]),
+            helper.complete_line("grant --", 8)
+        );
+        assert_eq!(
+            (17, vec![]),
+            helper.complete_line("grant bob reader extra", 22)
+        );
+
+        // Completers can only be added for the command's own parameters
+        let command: Command<Vec<String>, Error> = Command::new("grant", foo);
+        assert_eq!(
+            Err(Error::UnknownParameter("grant".into(), "user".into())),
+            command.with_completer("user", |_: &Vec<String>, _| vec![])
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hints() -> Result<()> {
+        let helper = helper(vec![
+            Command::group("user").with_subcommand(
+                Command::new("add", foo)
+                    .with_parameter(Parameter::new("name").set_required(true)?)?
+                    .with_parameter(Parameter::new("groups").set_variadic(0, None)?)?
+                    .with_parameter(Parameter::new("admin").set_flag(true)?)?,
+            ),
+            Command::new("foo", foo),
+        ]);
+
+        assert_eq!(
+            Some(" <subcommand>".to_string()),
+            helper.hint_line("user", 4)
+        );
+        assert_eq!(
+            Some("name [groups...] [--admin]".to_string()),
+            helper.hint_line("user add ", 9)
+        );
+        assert_eq!(
+            Some(" [groups...]".to_string()),
+            helper.hint_line("user add --admin bob", 20)
+        );
+        assert_eq!(None, helper.hint_line("user add ", 4));
+        assert_eq!(None, helper.hint_line("us", 2));
+        assert_eq!(None, helper.hint_line("foo ", 4));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_highlighting() {
+        let helper = helper(vec![
+            Command::group("user").with_subcommand(Command::new("add", foo)),
+            Command::new("foo", foo),
+        ]);
+        let green = |word: &str| Paint::green(word).to_string();
+        let yellow = |word: &str| Paint::yellow(word).to_string();
+
+        assert_eq!(
+            format!("{} {} bob", green("user"), green("add")),
+            helper.highlight_line("user add bob")
+        );
+        assert_eq!(
+            format!(
+                "  {} {} --name={}",
+                green("foo"),
+                yellow("'a b'"),
+                yellow(r#""c \" d""#)
+            ),
+            helper.highlight_line(r#"  foo 'a b' --name="c \" d""#)
+        );
+        assert_eq!(
+            format!("{} {}", Paint::red("bar"), yellow("\"unfinished")),
+            helper.highlight_line("bar \"unfinished")
+        );
+    }
+
+    #[test]
+    fn test_path_completion() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("repl-rs-complete-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        std::fs::write(dir.join("some_file.txt"), "")?;
+        let helper = helper(vec![Command::new("cat", foo).with_parameter(
+            Parameter::new("files")
+                .set_variadic(1, None)?
+                .set_type(ParameterType::Path)?,
+        )?]);
+
+        let line = format!("cat x {}/some_f", dir.display());
+        let result = helper.complete_line(&line, line.len());
+        std::fs::remove_dir_all(&dir)?;
+        assert_eq!(
+            (6, vec![format!("{}/some_file.txt", dir.display())]),
+            result
+        );
+
+        Ok(())
+    }
+}
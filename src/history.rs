@@ -0,0 +1,116 @@
+use crate::error::*;
+use rustyline::error::ReadlineError;
+use rustyline::Helper;
+use std::path::PathBuf;
+
+// Number of new history entries after which the history file is saved
+const SAVE_INTERVAL: usize = 10;
+
+type Predicate = Box<dyn Fn(&str) -> bool>;
+
+// Settings for the line editor's command history, and whether it persists across sessions
+pub(crate) struct History {
+    pub(crate) file: Option<PathBuf>,
+    pub(crate) max_size: usize,
+    pub(crate) ignore_dups: bool,
+    pub(crate) exclude: Option<Predicate>,
+    unsaved: usize,
+}
+
+impl History {
+    pub(crate) fn new() -> Self {
+        Self {
+            file: None,
+            max_size: 100,
+            ignore_dups: true,
+            exclude: None,
+            unsaved: 0,
+        }
+    }
+
+    pub(crate) fn config(&self) -> rustyline::Config {
+        rustyline::Config::builder()
+            .max_history_size(self.max_size)
+            .history_ignore_dups(self.ignore_dups)
+            .build()
+    }
+
+    // Load the history file, if there is one. It not existing yet isn't an error.
+    pub(crate) fn load<H: Helper>(&mut self, editor: &mut rustyline::Editor<H>) -> Result<()> {
+        if let Some(file) = &self.file {
+            match editor.load_history(file) {
+                Ok(()) => (),
+                Err(ReadlineError::Io(error)) if error.kind() == std::io::ErrorKind::NotFound => (),
+                Err(error) => return Err(io_error(error)),
+            }
+        }
+        self.unsaved = 0;
+
+        Ok(())
+    }
+
+    // Add a line to the history, unless it's excluded, saving the history file every so often
+    pub(crate) fn add<H: Helper>(
+        &mut self,
+        editor: &mut rustyline::Editor<H>,
+        line: &str,
+    ) -> Result<()> {
+        if line.trim().is_empty() || self.exclude.as_ref().is_some_and(|exclude| exclude(line)) {
+            return Ok(());
+        }
+        if editor.add_history_entry(line) {
+            self.unsaved += 1;
+            if self.unsaved >= SAVE_INTERVAL {
+                self.save(editor)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn save<H: Helper>(&mut self, editor: &mut rustyline::Editor<H>) -> Result<()> {
+        if let Some(file) = &self.file {
+            editor.save_history(file).map_err(io_error)?;
+        }
+        self.unsaved = 0;
+
+        Ok(())
+    }
+}
+
+fn io_error(error: ReadlineError) -> Error {
+    match error {
+        ReadlineError::Io(error) => error.into(),
+        error => Error::IoError(std::io::ErrorKind::Other, error.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::*;
+    use crate::history::History;
+
+    #[test]
+    fn test_history_persists() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("repl-rs-history-{}", std::process::id()));
+        let mut history = History::new();
+        history.file = Some(path.clone());
+        history.max_size = 3;
+        history.exclude = Some(Box::new(|line| line.contains("password")));
+
+        let mut editor: rustyline::Editor<()> = rustyline::Editor::with_config(history.config());
+        history.load(&mut editor)?;
+        for line in &["one", "two", "two", "login password", "  ", "three", "four"] {
+            history.add(&mut editor, line)?;
+        }
+        history.save(&mut editor)?;
+
+        let mut editor: rustyline::Editor<()> = rustyline::Editor::with_config(history.config());
+        history.load(&mut editor)?;
+        std::fs::remove_file(&path)?;
+        let lines = editor.history().iter().cloned().collect::<Vec<String>>();
+        assert_eq!(vec!["two", "three", "four"], lines);
+
+        Ok(())
+    }
+}
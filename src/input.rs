@@ -0,0 +1,127 @@
+use crate::error::*;
+use crate::lexer;
+
+// A command read from one or more lines: its arguments, and the body given after them if the
+// command takes one
+#[derive(Debug, PartialEq)]
+pub(crate) struct Input {
+    pub(crate) args: Vec<String>,
+    pub(crate) body: Option<String>,
+}
+
+// Parse input which may span several lines. If the arguments on the first line (or lines,
+// when they continue with quotes or backslashes) end with `<<TERMINATOR`, and `takes_body` says
+// the command they name takes a body, the lines after them up to one containing only the
+// terminator are the body. Otherwise all of the input is arguments.
+pub(crate) fn parse<F>(input: &str, takes_body: F) -> Result<Input>
+where
+    F: Fn(&[String]) -> bool,
+{
+    let mut end = 0;
+    for line in input.split_inclusive('\n') {
+        end += line.len();
+        let mut args = match lexer::tokenize(&input[..end]) {
+            Ok(args) if !continues(&input[..end]) => args,
+            _ => continue,
+        };
+        let terminator = match args.last().and_then(|last| last.strip_prefix("<<")) {
+            Some(terminator) if !terminator.is_empty() => terminator.to_string(),
+            _ => break,
+        };
+        args.pop();
+        if !takes_body(&args) {
+            break;
+        }
+        let mut body = String::new();
+        for line in input[end..].split_inclusive('\n') {
+            if line.trim_end_matches(['\n', '\r']) == terminator {
+                return Ok(Input {
+                    args,
+                    body: Some(body),
+                });
+            }
+            body.push_str(line);
+        }
+        return Err(Error::UnterminatedBody(terminator));
+    }
+
+    Ok(Input {
+        args: lexer::tokenize(input)?,
+        body: None,
+    })
+}
+
+// Whether the input is complete, or more lines need to be read because a quote or a body
+// hasn't been ended, or the last line ends with a backslash
+pub(crate) fn is_complete<F>(input: &str, takes_body: F) -> bool
+where
+    F: Fn(&[String]) -> bool,
+{
+    !continues(input)
+        && !matches!(
+            parse(input, takes_body),
+            Err(Error::UnterminatedQuote(_)) | Err(Error::UnterminatedBody(_))
+        )
+}
+
+// Whether the input ends with a backslash which isn't itself escaped
+fn continues(input: &str) -> bool {
+    let input = input.strip_suffix('\n').unwrap_or(input);
+    input.chars().rev().take_while(|c| *c == '\\').count() % 2 == 1
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::*;
+    use crate::input::{is_complete, parse, Input};
+
+    fn takes_body(args: &[String]) -> bool {
+        args.first().map(String::as_str) == Some("write")
+    }
+
+    #[test]
+    fn test_body() -> Result<()> {
+        assert_eq!(
+            Input {
+                args: vec!["write".into(), "notes.txt".into()],
+                body: Some("first line\n  second 'line'\n".into()),
+            },
+            parse(
+                "write notes.txt <<END\nfirst line\n  second 'line'\nEND",
+                takes_body
+            )?
+        );
+        assert_eq!(
+            Input {
+                args: vec!["write".into(), "a b".into()],
+                body: Some(String::new()),
+            },
+            parse("write \"a \\\nb\" \\\n<<EOF\nEOF\n", takes_body)?
+        );
+        assert_eq!(
+            Input {
+                args: vec!["echo".into(), "<<END".into(), "x".into()],
+                body: None,
+            },
+            parse("echo <<END\nx", takes_body)?
+        );
+        assert_eq!(
+            Err(Error::UnterminatedBody("END".into())),
+            parse("write <<END\nnever ended", takes_body)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_is_complete() {
+        assert!(is_complete("echo 'a b' c", takes_body));
+        assert!(is_complete("echo c\\\\", takes_body));
+        assert!(is_complete("write <<END\nbody\nEND", takes_body));
+        assert!(!is_complete("echo 'a b", takes_body));
+        assert!(!is_complete("echo \"a\nb", takes_body));
+        assert!(!is_complete("echo a \\", takes_body));
+        assert!(!is_complete("write <<END\nbody", takes_body));
+        assert!(!is_complete("write <<END\nbody\\\n", takes_body));
+    }
+}
@@ -0,0 +1,143 @@
+use crate::error::*;
+
+// Splits a line into arguments, using rules like those of a POSIX shell:
+// - arguments are separated by unquoted whitespace
+// - within single quotes, everything is taken literally
+// - within double quotes, whitespace and single quotes are taken literally, and backslash
+//   escapes are recognized
+// - outside of quotes, a backslash escapes the character after it
+// - quoted and unquoted parts next to each other are joined into a single argument, and an
+//   empty pair of quotes gives an empty argument
+// The escape sequences `\n`, `\t`, `\r` and `\0` give the corresponding control characters.
+// Within double quotes, a backslash before any other character except `"` or `\` is kept.
+// Outside of single quotes, a backslash at the end of a line joins it to the next one.
+pub(crate) fn tokenize(line: &str) -> Result<Vec<String>> {
+    let mut tokens = vec![];
+    let mut current: Option<String> = None;
+    let mut chars = line.chars().enumerate();
+    while let Some((index, c)) = chars.next() {
+        // Column of the current character, starting at 1, for errors
+        let column = index + 1;
+        match c {
+            c if c.is_whitespace() => {
+                if let Some(token) = current.take() {
+                    tokens.push(token);
+                }
+            }
+            '\'' => {
+                let token = current.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some((_, '\'')) => break,
+                        Some((_, c)) => token.push(c),
+                        None => return Err(Error::UnterminatedQuote(column)),
+                    }
+                }
+            }
+            '"' => {
+                let token = current.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some((_, '"')) => break,
+                        Some((_, '\\')) => match chars.next() {
+                            Some((_, '\n')) => (),
+                            Some((_, c)) => match escape(c) {
+                                Some(escaped) => token.push(escaped),
+                                None => {
+                                    token.push('\\');
+                                    token.push(c);
+                                }
+                            },
+                            None => return Err(Error::UnterminatedQuote(column)),
+                        },
+                        Some((_, c)) => token.push(c),
+                        None => return Err(Error::UnterminatedQuote(column)),
+                    }
+                }
+            }
+            '\\' => match chars.next() {
+                Some((_, '\n')) => (),
+                Some((_, c)) => current
+                    .get_or_insert_with(String::new)
+                    .push(escape(c).unwrap_or(c)),
+                None => current.get_or_insert_with(String::new).push('\\'),
+            },
+            c => current.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if let Some(token) = current.take() {
+        tokens.push(token);
+    }
+
+    Ok(tokens)
+}
+
+fn escape(c: char) -> Option<char> {
+    match c {
+        'n' => Some('\n'),
+        't' => Some('\t'),
+        'r' => Some('\r'),
+        '0' => Some('\0'),
+        '\\' | '"' => Some(c),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::*;
+    use crate::lexer::tokenize;
+
+    #[test]
+    fn test_splits_on_whitespace() -> Result<()> {
+        assert_eq!(vec!["foo", "bar", "baz"], tokenize("  foo bar\tbaz  ")?);
+        assert!(tokenize("   ")?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_quotes() -> Result<()> {
+        assert_eq!(vec!["foo", "bar baz", ""], tokenize(r#"foo "bar baz" """#)?);
+        assert_eq!(vec!["it's", "a \\n b"], tokenize(r#""it's" 'a \n b'"#)?);
+        assert_eq!(vec!["foobar baz"], tokenize(r#"foo"bar baz""#)?);
+        assert_eq!(vec!["", "x"], tokenize("'' x")?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_escapes() -> Result<()> {
+        assert_eq!(
+            vec!["foo bar", "\"quoted\""],
+            tokenize(r#"foo\ bar \"quoted\""#)?
+        );
+        assert_eq!(vec!["a\tb", "c\\d", "e\\"], tokenize(r#""a\tb" "c\d" e\"#)?);
+        assert_eq!(vec!["line\nbreak"], tokenize(r#"line\nbreak"#)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_line_continuation() -> Result<()> {
+        assert_eq!(vec!["foo", "bar"], tokenize("foo \\\nbar")?);
+        assert_eq!(vec!["foobar"], tokenize("foo\\\nbar")?);
+        assert_eq!(vec!["a b\nc d"], tokenize("\"a b\\\n\nc d\"")?);
+        assert_eq!(vec!["a\\\nb"], tokenize("'a\\\nb'")?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unterminated_quote_fails() {
+        assert_eq!(
+            Err(Error::UnterminatedQuote(5)),
+            tokenize(r#"foo "bar baz"#)
+        );
+        assert_eq!(
+            Err(Error::UnterminatedQuote(11)),
+            tokenize(r#"foo "bar" 'baz"#)
+        );
+        assert_eq!(Err(Error::UnterminatedQuote(1)), tokenize(r#""foo\""#));
+    }
+}
@@ -32,7 +32,7 @@
 //! - with a name of "MyApp", the given version, and the given description
 //! - and adding a "hello" command which calls out to the `hello` callback function defined above
 //! - the `hello` command has a single parameter, "who", which is required, and has the given help
-//! message
+//!   message
 //!
 //! The `hello` function takes a HashMap of named arguments, contained in a
 //! [Value](struct.Value.html) struct, and an (unused) `Context`, which is used to hold state if you
@@ -80,6 +80,155 @@
 //! ```
 //! This example adds two numbers. The `convert()` function manages the conversion for you.
 //!
+//! You can also give a parameter a [ParameterType](enum.ParameterType.html), so that bad input
+//! is rejected before your callback is ever called:
+//! ```
+//! use repl_rs::{Command, Parameter, ParameterType, Result, Value};
+//! use repl_rs::{Convert, Repl};
+//! use std::collections::HashMap;
+//!
+//! fn double<T>(args: HashMap<String, Value>, _context: &mut T) -> Result<Option<String>> {
+//!     let number: i64 = args["number"].convert()?;
+//!
+//!     Ok(Some((number * 2).to_string()))
+//! }
+//!
+//! fn main() -> Result<()> {
+//!     let mut repl = Repl::new(()).add_command(
+//!         Command::new("double", double)
+//!             .with_parameter(
+//!                 Parameter::new("number")
+//!                     .set_required(true)?
+//!                     .set_type(ParameterType::Int)?,
+//!             )?
+//!             .with_help("Double a number"),
+//!     );
+//!     repl.run()
+//! }
+//! ```
+//! Typing `double abc` then gives you
+//! `Error: Invalid value 'abc' for argument 'number', expected int`.
+//!
+//! # Options and flags
+//!
+//! Parameters are positional by default. Giving a parameter a long or short name turns it into
+//! a named option, which can appear anywhere on the line:
+//! ```
+//! use repl_rs::{Command, Parameter, ParameterType, Result, Value};
+//! use repl_rs::{Convert, Repl};
+//! use std::collections::HashMap;
+//!
+//! fn show<T>(args: HashMap<String, Value>, _context: &mut T) -> Result<Option<String>> {
+//!     let verbose: bool = args["verbose"].convert()?;
+//!     let lines: usize = args["lines"].convert()?;
+//!     let tags: Vec<String> = match args.get("tag") {
+//!         Some(tag) => tag.convert()?,
+//!         None => vec![],
+//!     };
+//!
+//!     Ok(Some(format!("{} {} {} {:?}", args["file"], verbose, lines, tags)))
+//! }
+//!
+//! fn main() -> Result<()> {
+//!     let mut repl = Repl::new(()).add_command(
+//!         Command::new("show", show)
+//!             .with_parameter(Parameter::new("file").set_required(true)?)?
+//!             .with_parameter(Parameter::new("verbose").set_flag(true)?.set_short('v'))?
+//!             .with_parameter(
+//!                 Parameter::new("lines")
+//!                     .set_long("lines")
+//!                     .set_short('n')
+//!                     .set_type(ParameterType::Int)?
+//!                     .set_default("10")?,
+//!             )?
+//!             .with_parameter(Parameter::new("tag").set_long("tag").set_multiple(true))?
+//!             .with_help("Show a file"),
+//!     );
+//!     repl.run()
+//! }
+//! ```
+//! With this, `show -v foo.txt -n 5 --tag a --tag=b` works as you'd expect. Flags are always
+//! present in the arguments, as `true` or `false`, and options which can be repeated are
+//! collected into a list, which converts into a `Vec`. An argument of `--` ends the options, so
+//! anything after it is positional.
+//!
+//! The last positional parameter can also be variadic, taking a range of arguments, so
+//! `Parameter::new("files").set_variadic(1, None)?` accepts one or more files. These are also
+//! collected into a list, and shown as `files...` in the help.
+//!
+//! # Completion
+//!
+//! With [.use_completion(true)](struct.Repl.html#method.use_completion), hitting tab completes
+//! command and subcommand names, long option names, and argument values. Values are completed
+//! from the parameter's [choices](struct.Parameter.html#method.set_choices), as file paths for
+//! [ParameterType::Path](enum.ParameterType.html#variant.Path) parameters, or using a function
+//! you give the command with [.with_completer()](struct.Command.html#method.with_completer),
+//! which gets your context and what's been typed so far.
+//!
+//! Command names, including the built-in `help`, `exit` and `quit`, are matched by prefix by
+//! default. [.with_match_strategy()](struct.Repl.html#method.with_match_strategy) lets you match
+//! ignoring case, or fuzzily, so that `lsu` completes `list-users`; the best matches come first.
+//!
+//! [.use_hints(true)](struct.Repl.html#method.use_hints) shows the parameters still to be given
+//! greyed out after the cursor, and
+//! [.use_highlighting(true)](struct.Repl.html#method.use_highlighting) colors the line as it's
+//! typed, so a mistyped command shows up in red before you hit enter.
+//!
+//! # Subcommands
+//!
+//! Commands can have subcommands, so you can group related verbs under a noun:
+//! ```
+//! use repl_rs::{Command, Parameter, Result, Value};
+//! use repl_rs::Repl;
+//! use std::collections::HashMap;
+//!
+//! fn add_user<T>(args: HashMap<String, Value>, _context: &mut T) -> Result<Option<String>> {
+//!     Ok(Some(format!("Added {}", args["name"])))
+//! }
+//!
+//! fn list_users<T>(_args: HashMap<String, Value>, _context: &mut T) -> Result<Option<String>> {
+//!     Ok(Some("Nobody yet".to_string()))
+//! }
+//!
+//! fn main() -> Result<()> {
+//!     let mut repl = Repl::new(()).add_command(
+//!         Command::group("user")
+//!             .with_subcommand(
+//!                 Command::new("add", add_user)
+//!                     .with_parameter(Parameter::new("name").set_required(true)?)?
+//!                     .with_help("Add a user"),
+//!             )
+//!             .with_subcommand(Command::new("list", list_users).with_help("List users"))
+//!             .with_help("Manage users"),
+//!     );
+//!     repl.run()
+//! }
+//! ```
+//! Here `user add bob` runs `add_user`, and `help user` lists the subcommands of `user`.
+//!
+//! # Aliases
+//!
+//! A command or subcommand can have other names, given with
+//! [.with_alias()](struct.Command.html#method.with_alias), so
+//! `Command::new("list", list_users).with_alias("ls")` can also be run as `ls`. Users can add
+//! their own shortcuts at runtime with the built-in `alias` command: `alias ll list --long`
+//! makes `ll /tmp` run `list --long /tmp`, and `alias` on its own lists them. Aliases are shown
+//! in the help and completed with tab.
+//!
+//! # Multi-line input
+//!
+//! A command carries on over the next line when a quote is left open or the line ends with a
+//! backslash, both at the prompt and in [scripts](#scripts). A command can also take a
+//! heredoc-style body with [.with_body()](struct.Command.html#method.with_body):
+//! with `Command::new("write", write).with_body("text")`, typing
+//! ```text
+//! write notes.txt <<END
+//! first line
+//! second line
+//! END
+//! ```
+//! passes `"first line\nsecond line\n"` to `write` as `args["text"]`.
+//!
 //! # Context
 //!
 //! The `Context` type is used to keep state between REPL calls. Here's an example:
@@ -128,9 +277,212 @@
 //! ```
 //! A few things to note:
 //! - you pass in the initial value for your Context struct to the call to
-//! [Repl::new()](struct.Repl.html#method.new)
+//!   [Repl::new()](struct.Repl.html#method.new)
 //! - the context is passed to your command callback functions as a mutable reference
 //!
+//! # Closures
+//!
+//! Command callbacks don't have to be plain functions. Anything implementing
+//! [CommandHandler](trait.CommandHandler.html) can be used, which includes closures, so you can
+//! capture configuration, handles or channels:
+//! ```
+//! use repl_rs::{Command, Parameter, Result, Value};
+//! use repl_rs::Repl;
+//! use std::collections::HashMap;
+//!
+//! fn main() -> Result<()> {
+//!     let greeting = String::from("Howdy");
+//!     let mut repl = Repl::new(())
+//!         .add_command(
+//!             Command::new(
+//!                 "greet",
+//!                 move |args: HashMap<String, Value>,
+//!                       _context: &mut ()|
+//!                       -> Result<Option<String>> {
+//!                     Ok(Some(format!("{}, {}", greeting, args["who"])))
+//!                 },
+//!             )
+//!             .with_parameter(Parameter::new("who").set_required(true)?)?
+//!             .with_help("Greetings!"),
+//!         );
+//!     repl.run()
+//! }
+//! ```
+//! Note that the closure's argument and return types need to be spelled out.
+//!
+//! # Deriving commands
+//!
+//! With the `derive` feature turned on, you can declare a command's arguments as a struct and
+//! `#[derive(ReplCommand)]` on it, instead of adding each parameter and converting each
+//! argument by hand. The parameters come from the fields and their types, the help from the
+//! doc comments, and the handler you pass to
+//! [Command::typed](struct.Command.html#method.typed) gets the struct:
+//! ```ignore
+//! use repl_rs::{Command, Repl, ReplCommand, Result};
+//!
+//! /// Greet someone
+//! #[derive(ReplCommand)]
+//! struct Hello {
+//!     /// Who to greet
+//!     who: String,
+//!
+//!     /// How many times
+//!     #[repl(short = 'n', long, default = "1")]
+//!     times: usize,
+//! }
+//!
+//! fn hello(args: Hello, _context: &mut ()) -> Result<Option<String>> {
+//!     Ok(Some(format!("Hello, {}! ", args.who).repeat(args.times)))
+//! }
+//!
+//! fn main() -> Result<()> {
+//!     let mut repl = Repl::new(()).add_command(Command::typed(hello)?);
+//!     repl.run()
+//! }
+//! ```
+//! See the [repl-rs-derive](https://docs.rs/repl-rs-derive) documentation for the attributes
+//! it takes.
+//!
+//! # Clap commands
+//!
+//! If your program already has a command line defined with [clap](https://docs.rs/clap), you
+//! can turn its commands into REPL commands with
+//! [Command::from_clap](struct.Command.html#method.from_clap), rather than defining them twice.
+//! Each line is parsed by clap, and the handler is passed the `ArgMatches`, so the code which
+//! runs your command line can run the REPL's commands too:
+//! ```no_run
+//! use repl_rs::{Arg, ArgMatches, Command, Repl, Result};
+//!
+//! fn remote(matches: ArgMatches, _context: &mut ()) -> Result<Option<String>> {
+//!     Ok(match matches.subcommand() {
+//!         Some(("add", matches)) => matches.get_one::<String>("name").cloned(),
+//!         _ => None,
+//!     })
+//! }
+//!
+//! fn main() -> Result<()> {
+//!     let remote_command = clap::Command::new("remote")
+//!         .about("Manage remotes")
+//!         .subcommand(clap::Command::new("add").arg(Arg::new("name").required(true)));
+//!     let mut repl = Repl::new(()).add_command(Command::from_clap(remote_command, remote));
+//!     repl.run()
+//! }
+//! ```
+//! The help and tab completion come from the clap command's subcommands and arguments.
+//!
+//! If the whole command line is a set of subcommands, like `git` or `cargo`,
+//! [Repl::add_clap_subcommands](struct.Repl.html#method.add_clap_subcommands) adds each of them
+//! as a command of its own instead, so they're run at the prompt without the program's name in
+//! front. The handler is passed the same `ArgMatches` as when the program is run from the
+//! command line:
+//! ```no_run
+//! use repl_rs::{Arg, ArgMatches, Repl, Result};
+//!
+//! fn run(matches: ArgMatches, _context: &mut ()) -> Result<Option<String>> {
+//!     Ok(match matches.subcommand() {
+//!         Some(("add", matches)) => matches.get_one::<String>("name").cloned(),
+//!         _ => None,
+//!     })
+//! }
+//!
+//! fn main() -> Result<()> {
+//!     let cli = clap::Command::new("remote")
+//!         .subcommand(clap::Command::new("add").arg(Arg::new("name").required(true)))
+//!         .subcommand(clap::Command::new("list"));
+//!     let matches = cli.clone().get_matches();
+//!     match matches.subcommand() {
+//!         Some(_) => run(matches, &mut ()).map(|_| ()),
+//!         None => Repl::new(()).add_clap_subcommands(cli, run).run(),
+//!     }
+//! }
+//! ```
+//!
+//! # Async commands
+//!
+//! With the `async` feature turned on, commands can be async, so that clients built on tokio
+//! don't have to block inside each command. Create them with
+//! [Command::new_async](struct.Command.html#method.new_async), whose handler returns a boxed
+//! future, and run the Repl with [run_async](struct.Repl.html#method.run_async) rather than
+//! `run`. Ordinary commands can be mixed in with async ones. The line editor runs on a
+//! blocking thread, so the runtime carries on with other tasks while it's waiting for input,
+//! which means `run_async` needs tokio's multi-threaded runtime. Pressing Ctrl-C while a
+//! command is running drops its future:
+//! ```ignore
+//! use repl_rs::{Command, Repl, Result, Value};
+//! use std::collections::HashMap;
+//! use std::time::Duration;
+//!
+//! #[tokio::main]
+//! async fn main() -> Result<()> {
+//!     let mut repl = Repl::new(()).add_command(Command::new_async(
+//!         "wait",
+//!         |_args: HashMap<String, Value>, _context: &mut ()| {
+//!             Box::pin(async {
+//!                 tokio::time::sleep(Duration::from_secs(10)).await;
+//!                 Ok(Some("Done waiting".to_string()))
+//!             })
+//!         },
+//!     ));
+//!     repl.run_async().await
+//! }
+//! ```
+//!
+//! # Serving over a socket
+//!
+//! Besides the terminal, the Repl can be served to clients connecting over TCP, with
+//! [serve_tcp](struct.Repl.html#method.serve_tcp), or over a Unix domain socket, with
+//! [serve_unix](struct.Repl.html#method.serve_unix), for instance to attach to a running
+//! daemon's admin console with `nc` or `socat`. Each connection gets its own session, which
+//! uses a plain line protocol without any line editing, and runs against the same commands
+//! and context. Only one client is served at a time, and the others wait their turn, so
+//! [with_session_timeout](struct.Repl.html#method.with_session_timeout) ends the session of a
+//! client left idle:
+//! ```no_run
+//! use repl_rs::{Repl, Result};
+//! use std::net::TcpListener;
+//! use std::time::Duration;
+//!
+//! fn main() -> Result<()> {
+//!     let mut repl = Repl::<(), repl_rs::Error>::new(())
+//!         .with_name("admin")
+//!         .with_session_timeout(Duration::from_secs(300));
+//!     repl.serve_tcp(TcpListener::bind("127.0.0.1:7777")?)
+//! }
+//! ```
+//! To serve several clients at once, see the next section.
+//!
+//! # Sharing commands between sessions
+//!
+//! A [Registry](struct.Registry.html) holds what's the same for every session: the name,
+//! version and description, and functions making the commands. It can be shared in an `Arc` by
+//! Repls on other threads, made with [Repl::with_registry](struct.Repl.html#method.with_registry),
+//! each with its own context and its own copy of the commands, so only the functions making
+//! them need to be `Send` and `Sync`, not the handlers.
+//! [Registry::serve_tcp](struct.Registry.html#method.serve_tcp) and
+//! [Registry::serve_unix](struct.Registry.html#method.serve_unix) use this to serve each
+//! connection's session on a thread of its own:
+//! ```no_run
+//! use repl_rs::{Command, Registry, Repl, Result, Value};
+//! use std::collections::HashMap;
+//! use std::net::TcpListener;
+//! use std::sync::Arc;
+//!
+//! // Count how many times this session has run the command
+//! fn count(_args: HashMap<String, Value>, count: &mut usize) -> Result<Option<String>> {
+//!     *count += 1;
+//!     Ok(Some(count.to_string()))
+//! }
+//!
+//! fn main() -> Result<()> {
+//!     let registry = Registry::new()
+//!         .with_name("admin")
+//!         .add_command(|_, _| Command::new("count", count));
+//!     Arc::new(registry).serve_tcp(TcpListener::bind("127.0.0.1:7777")?, |registry| {
+//!         Repl::with_registry(registry, 0)
+//!     })
+//! }
+//! ```
+//!
 //! # The "initialize_repl" macro
 //! Instead of hardcoding your package name, version and description in your code, you can instead
 //! use those values from your `Cargo.toml` file, using the `initialize_repl` macro:
@@ -186,6 +538,72 @@
 //! Note the `#[macro_use] extern crate clap` at the top. You'll need that in order to avoid
 //! getting messages like `error: cannot find macro 'crate_name' in this scope`.
 //!
+//! # History
+//!
+//! By default the command history only lasts as long as the session. To keep it across sessions,
+//! give the Repl a file to keep it in:
+//! ```no_run
+//! # use repl_rs::{Repl, Result};
+//! # fn main() -> Result<()> {
+//! let mut repl = Repl::<(), repl_rs::Error>::new(())
+//!     .with_history_file(".myapp_history")
+//!     .with_history_size(500)
+//!     .exclude_from_history(|line| line.starts_with("login"));
+//! repl.run()
+//! # }
+//! ```
+//! Lines the predicate passed to
+//! [.exclude_from_history()](struct.Repl.html#method.exclude_from_history) returns true for,
+//! like commands containing passwords, are never added to the history.
+//!
+//! # Scripts
+//!
+//! The same commands can be run non-interactively, from a file with
+//! [Repl::run_script](struct.Repl.html#method.run_script) or from anything implementing `BufRead`
+//! with [Repl::run_reader](struct.Repl.html#method.run_reader). There's no line editing or welcome
+//! banner, blank lines and `#` comments are skipped, and you get back a
+//! [ScriptSummary](struct.ScriptSummary.html) of the lines which failed:
+//! ```
+//! use repl_rs::{Command, Parameter, Result, Value};
+//! use repl_rs::Repl;
+//! use std::collections::HashMap;
+//!
+//! fn hello<T>(args: HashMap<String, Value>, _context: &mut T) -> Result<Option<String>> {
+//!     Ok(Some(format!("Hello, {}", args["who"])))
+//! }
+//!
+//! fn main() -> Result<()> {
+//!     let mut repl = Repl::new(())
+//!         .stop_on_error(false)
+//!         .add_command(
+//!             Command::new("hello", hello)
+//!                 .with_parameter(Parameter::new("who").set_required(true)?)?,
+//!         );
+//!     let summary = repl.run_reader(std::io::stdin().lock())?;
+//!     if !summary.is_success() {
+//!         std::process::exit(1);
+//!     }
+//!     Ok(())
+//! }
+//! ```
+//!
+//! If you want to drive the Repl yourself, for instance from your own UI or from tests,
+//! [Repl::execute](struct.Repl.html#method.execute) runs a single line and returns whatever the
+//! command returned, rather than printing it:
+//! ```
+//! # use repl_rs::{Command, Result, Value};
+//! # use repl_rs::Repl;
+//! # use std::collections::HashMap;
+//! # fn hello<T>(_args: HashMap<String, Value>, _context: &mut T) -> Result<Option<String>> {
+//! #     Ok(Some("Hello!".to_string()))
+//! # }
+//! # fn main() -> Result<()> {
+//! let mut repl = Repl::new(()).add_command(Command::new("hello", hello));
+//! assert_eq!(Some("Hello!".to_string()), repl.execute("hello")?);
+//! # Ok(())
+//! # }
+//! ```
+//!
 //! # Help
 //! repl-rs has support for supplying help commands for your REPL. This is accomplished through the
 //! [HelpViewer](trait.HelpViewer.html), which is a trait that has a default implementation which should give you pretty
@@ -202,11 +620,25 @@
 //! append: Append name to end of list
 //! Usage:
 //!         append name
+//! Arguments:
+//!         name  Name to append
 //! MyApp>
 //! ```
+//! The argument descriptions come from
+//! [Parameter::with_help()](struct.Parameter.html#method.with_help), and the table also shows
+//! each parameter's type, default and allowed values.
 //! If you want to roll your own help, just implement [HelpViewer](trait.HelpViewer.html) and add it to your REPL using the
 //! [.with_help_viewer()](struct.Repl.html#method.with_help_viewer) method.
 //!
+//! # Output
+//!
+//! Command output, help and the welcome banner go to stdout, and errors go to stderr, unless you
+//! give the Repl other sinks with [.with_output()](struct.Repl.html#method.with_output) and
+//! [.with_error_output()](struct.Repl.html#method.with_error_output). Anything implementing
+//! `std::io::Write` will do, so you can capture a transcript, send output to a GUI, or check it
+//! in your tests. The sinks are passed on to your [HelpViewer](trait.HelpViewer.html) and error
+//! handler.
+//!
 //! # Errors
 //!
 //! Your command functions don't need to return `repl_rs::Error`; you can return any error from
@@ -273,27 +705,52 @@
 //! }
 //! ```
 //!
+mod alias;
+mod cancel;
+mod clap_command;
 mod command;
+#[allow(hidden_glob_reexports)]
 mod error;
+mod exit;
 mod help;
+mod helper;
+mod history;
+mod input;
+mod lexer;
 mod parameter;
+mod prompt;
+mod registry;
 mod repl;
+mod script;
+mod suggest;
 mod value;
 
+pub use cancel::CancellationToken;
 pub use clap::*;
-pub use command::Command;
+#[cfg(feature = "async")]
+pub use command::CommandFuture;
+pub use command::{Command, CommandHandler, ReplCommand};
 pub use error::{Error, Result};
+pub use exit::ExitHandle;
 #[doc(inline)]
-pub use help::{HelpContext, HelpEntry, HelpViewer};
-pub use parameter::Parameter;
+pub use help::{HelpContext, HelpEntry, HelpViewer, ParameterHelp};
+pub use helper::MatchStrategy;
+pub use parameter::{CustomType, Parameter, ParameterType};
+pub use prompt::Prompt;
+pub use registry::Registry;
 #[doc(inline)]
 pub use repl::Repl;
+#[cfg(feature = "derive")]
+pub use repl_rs_derive::ReplCommand;
+pub use script::{ScriptFailure, ScriptSummary};
 #[doc(inline)]
 pub use value::{Convert, Value};
+pub use yansi::Paint;
 
 use std::collections::HashMap;
 
-/// Command callback function signature
+/// Command callback function signature. Any function or closure with this signature implements
+/// [CommandHandler](trait.CommandHandler.html)
 pub type Callback<Context, Error> =
     fn(HashMap<String, Value>, &mut Context) -> std::result::Result<Option<String>, Error>;
 
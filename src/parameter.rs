@@ -1,11 +1,109 @@
 use crate::error::*;
+use std::any::TypeId;
+use std::fmt;
+use std::str::FromStr;
+
+/// Type of a command parameter. Arguments are checked against their parameter's type before
+/// the command callback is called.
+#[derive(Clone, Debug)]
+pub enum ParameterType {
+    /// Any string (the default)
+    String,
+
+    /// Integer value
+    Int,
+
+    /// Floating point value
+    Float,
+
+    /// Boolean value, `true` or `false`
+    Bool,
+
+    /// Filesystem path
+    Path,
+
+    /// Custom type, made by [ParameterType::custom](#method.custom)
+    Custom(CustomType),
+}
+
+/// A custom parameter type, with a name to show in errors, a function which validates the
+/// argument, and the `TypeId` of the type the argument is meant to be, which tells custom types
+/// apart. Made by [ParameterType::custom](enum.ParameterType.html#method.custom).
+#[derive(Clone, Debug)]
+pub struct CustomType {
+    name: String,
+    validator: fn(&str) -> bool,
+    id: TypeId,
+}
+
+impl std::cmp::PartialEq for CustomType {
+    fn eq(&self, other: &CustomType) -> bool {
+        self.name == other.name && self.id == other.id
+    }
+}
+
+fn parses_as<T: FromStr>(value: &str) -> bool {
+    value.parse::<T>().is_ok()
+}
+
+impl ParameterType {
+    /// Create a custom parameter type which accepts any value that can be parsed into `T`
+    pub fn custom<T: 'static + FromStr>(name: &str) -> Self {
+        ParameterType::Custom(CustomType {
+            name: name.into(),
+            validator: parses_as::<T>,
+            id: TypeId::of::<T>(),
+        })
+    }
+
+    pub(crate) fn is_valid(&self, value: &str) -> bool {
+        match self {
+            ParameterType::String => true,
+            ParameterType::Int => parses_as::<i64>(value),
+            ParameterType::Float => parses_as::<f64>(value),
+            ParameterType::Bool => parses_as::<bool>(value),
+            ParameterType::Path => !value.is_empty(),
+            ParameterType::Custom(custom) => (custom.validator)(value),
+        }
+    }
+}
+
+impl fmt::Display for ParameterType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParameterType::String => write!(f, "string"),
+            ParameterType::Int => write!(f, "int"),
+            ParameterType::Float => write!(f, "float"),
+            ParameterType::Bool => write!(f, "bool"),
+            ParameterType::Path => write!(f, "path"),
+            ParameterType::Custom(custom) => write!(f, "{}", custom.name),
+        }
+    }
+}
+
+impl std::cmp::PartialEq for ParameterType {
+    fn eq(&self, other: &ParameterType) -> bool {
+        match (self, other) {
+            (ParameterType::Custom(custom), ParameterType::Custom(other)) => custom == other,
+            _ => std::mem::discriminant(self) == std::mem::discriminant(other),
+        }
+    }
+}
 
 /// Command parameter
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Parameter {
     pub(crate) name: String,
     pub(crate) required: bool,
     pub(crate) default: Option<String>,
+    pub(crate) kind: ParameterType,
+    pub(crate) long: Option<String>,
+    pub(crate) short: Option<char>,
+    pub(crate) flag: bool,
+    pub(crate) multiple: bool,
+    pub(crate) variadic: Option<(usize, Option<usize>)>,
+    pub(crate) choices: Vec<String>,
+    pub(crate) help: Option<String>,
 }
 
 impl Parameter {
@@ -15,13 +113,21 @@ impl Parameter {
             name: name.into(),
             required: false,
             default: None,
+            kind: ParameterType::String,
+            long: None,
+            short: None,
+            flag: false,
+            multiple: false,
+            variadic: None,
+            choices: vec![],
+            help: None,
         }
     }
 
     /// Set whether the parameter is required, default is not required.
     /// Note that you cannot have a required parameter after a non-required one
     pub fn set_required(mut self, required: bool) -> Result<Self> {
-        if self.default.is_some() {
+        if self.default.is_some() || self.flag || self.variadic.is_some() {
             return Err(Error::IllegalRequiredError(self.name));
         }
         self.required = required;
@@ -35,8 +141,169 @@ impl Parameter {
         if self.required {
             return Err(Error::IllegalDefaultError(self.name));
         }
+        self.validate(default)?;
         self.default = Some(default.to_string());
 
         Ok(self)
     }
+
+    /// Set the type of the parameter, default is [ParameterType::String](enum.ParameterType.html).
+    /// Arguments which don't match the type are rejected before the command is called
+    pub fn set_type(mut self, kind: ParameterType) -> Result<Self> {
+        self.kind = kind;
+        if let Some(default) = &self.default {
+            self.validate(default)?;
+        }
+
+        Ok(self)
+    }
+
+    /// Make this a named option which is given as `--<long> <value>` (or `--<long>=<value>`)
+    /// rather than by position
+    pub fn set_long(mut self, long: &str) -> Self {
+        self.long = Some(long.to_string());
+
+        self
+    }
+
+    /// Make this a named option which is given as `-<short> <value>` rather than by position
+    pub fn set_short(mut self, short: char) -> Self {
+        self.short = Some(short);
+
+        self
+    }
+
+    /// Make this a boolean switch, which takes no value and is `true` when present and `false`
+    /// otherwise. If no long or short name has been set, the parameter name is used as the long
+    /// name, with underscores changed to dashes. Note that a flag can't be required
+    pub fn set_flag(mut self, flag: bool) -> Result<Self> {
+        if flag && self.required {
+            return Err(Error::IllegalRequiredError(self.name));
+        }
+        self.flag = flag;
+        if flag {
+            if self.long.is_none() && self.short.is_none() {
+                self.long = Some(self.name.replace('_', "-"));
+            }
+            self.kind = ParameterType::Bool;
+        }
+
+        Ok(self)
+    }
+
+    /// Set whether a named option can be given more than once. All the values given are collected
+    /// into a list, which can be converted to a `Vec`
+    pub fn set_multiple(mut self, multiple: bool) -> Self {
+        self.multiple = multiple;
+
+        self
+    }
+
+    /// Make this a variadic parameter, which collects between `min` and `max` arguments (or any
+    /// number from `min` upwards if `max` is `None`) into a list which can be converted to a `Vec`.
+    /// A variadic parameter has to be the last positional parameter of its command, and is
+    /// required if `min` is more than zero
+    pub fn set_variadic(mut self, min: usize, max: Option<usize>) -> Result<Self> {
+        if max.is_some_and(|max| max < min.max(1)) || (min > 0 && self.default.is_some()) {
+            return Err(Error::IllegalVariadicError(self.name));
+        }
+        self.variadic = Some((min, max));
+        self.required = min > 0;
+
+        Ok(self)
+    }
+
+    /// Restrict the parameter to the given values. These are also offered when completing
+    /// the parameter with tab.
+    pub fn set_choices(mut self, choices: &[&str]) -> Result<Self> {
+        self.choices = choices.iter().map(|choice| choice.to_string()).collect();
+        if let Some(default) = &self.default {
+            self.validate(default)?;
+        }
+
+        Ok(self)
+    }
+
+    /// Add a description of the parameter, which is shown in the command's help
+    pub fn with_help(mut self, help: &str) -> Self {
+        self.help = Some(help.to_string());
+
+        self
+    }
+
+    pub(crate) fn is_positional(&self) -> bool {
+        self.long.is_none() && self.short.is_none()
+    }
+
+    /// The way this parameter is written in a usage line
+    pub(crate) fn usage(&self) -> String {
+        if self.is_positional() {
+            return match self.variadic {
+                Some(_) => format!("{}...", self.name),
+                None => self.name.clone(),
+            };
+        }
+        let mut names = vec![];
+        if let Some(short) = self.short {
+            names.push(format!("-{}", short));
+        }
+        if let Some(long) = &self.long {
+            names.push(format!("--{}", long));
+        }
+        let mut usage = names.join("|");
+        if !self.flag {
+            usage = format!("{} <{}>", usage, self.name);
+        }
+        if self.multiple {
+            usage.push_str("...");
+        }
+
+        usage
+    }
+
+    pub(crate) fn validate(&self, value: &str) -> Result<()> {
+        if !self.choices.is_empty() && !self.choices.iter().any(|choice| choice == value) {
+            Err(Error::InvalidArgument(
+                self.name.clone(),
+                format!("one of {}", self.choices.join(", ")),
+                value.to_string(),
+            ))
+        } else if self.kind.is_valid(value) {
+            Ok(())
+        } else {
+            Err(Error::InvalidArgument(
+                self.name.clone(),
+                self.kind.to_string(),
+                value.to_string(),
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ParameterType;
+    use std::net::{IpAddr, SocketAddr};
+
+    #[test]
+    fn test_parameter_type_eq() {
+        assert_eq!(ParameterType::Int, ParameterType::Int);
+        assert_ne!(ParameterType::Int, ParameterType::Float);
+        assert_eq!(
+            ParameterType::custom::<IpAddr>("address"),
+            ParameterType::custom::<IpAddr>("address")
+        );
+        assert_ne!(
+            ParameterType::custom::<IpAddr>("address"),
+            ParameterType::custom::<SocketAddr>("address")
+        );
+        assert_ne!(
+            ParameterType::custom::<IpAddr>("address"),
+            ParameterType::custom::<IpAddr>("ip")
+        );
+        assert_ne!(
+            ParameterType::custom::<String>("string"),
+            ParameterType::String
+        );
+    }
 }
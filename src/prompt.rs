@@ -0,0 +1,89 @@
+use std::fmt;
+use std::fmt::Display;
+
+/// A prompt made up of segments, each of which can be colored or styled on its own with
+/// [Paint](struct.Paint.html), for use with
+/// [Repl::with_prompt_fn](struct.Repl.html#method.with_prompt_fn). The segments are joined with
+/// the separator, if there is one, and followed by the suffix:
+/// ```
+/// use repl_rs::{Paint, Prompt, Repl};
+///
+/// struct Context {
+///     user: String,
+///     directory: String,
+/// }
+///
+/// let repl = Repl::<Context, repl_rs::Error>::new(Context {
+///     user: "root".into(),
+///     directory: "/tmp".into(),
+/// })
+/// .with_prompt_fn(|context: &Context| {
+///     Prompt::new()
+///         .with_separator(":")
+///         .with_suffix("> ")
+///         .segment(Paint::green(&context.user).bold())
+///         .segment(Paint::blue(&context.directory))
+///         .to_string()
+/// });
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Prompt {
+    segments: Vec<String>,
+    separator: String,
+    suffix: String,
+}
+
+impl Prompt {
+    /// Create a prompt with no segments, separator or suffix
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set what's written between each pair of segments
+    pub fn with_separator(mut self, separator: &str) -> Self {
+        self.separator = separator.to_string();
+
+        self
+    }
+
+    /// Set what's written after the last segment, usually something like `"> "`
+    pub fn with_suffix(mut self, suffix: &str) -> Self {
+        self.suffix = suffix.to_string();
+
+        self
+    }
+
+    /// Add a segment, which is anything displayable: plain text, or text painted with
+    /// [Paint](struct.Paint.html)
+    pub fn segment<T: Display>(mut self, segment: T) -> Self {
+        self.segments.push(segment.to_string());
+
+        self
+    }
+}
+
+impl Display for Prompt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", self.segments.join(&self.separator), self.suffix)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Paint, Prompt};
+
+    #[test]
+    fn test_segments() {
+        assert_eq!("", Prompt::new().to_string());
+        assert_eq!(
+            "\u{1b}[32mdb\u{1b}[0m:\u{1b}[1;36mlocal\u{1b}[0m:plain> ",
+            Prompt::new()
+                .with_separator(":")
+                .with_suffix("> ")
+                .segment(Paint::green("db"))
+                .segment(Paint::cyan("local").bold())
+                .segment("plain")
+                .to_string()
+        );
+    }
+}
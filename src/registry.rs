@@ -0,0 +1,214 @@
+use crate::error::*;
+use crate::repl::Connection;
+use crate::{CancellationToken, Command, ExitHandle, Repl};
+use std::fmt::Display;
+use std::io::{self, Write};
+use std::net::TcpListener;
+#[cfg(unix)]
+use std::os::unix::net::UnixListener;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+// Function making one of a registry's commands, called for each session with its handles
+pub(crate) type CommandFn<Context, E> =
+    Box<dyn Fn(ExitHandle, CancellationToken) -> Command<Context, E> + Send + Sync>;
+
+// Longest wait before accepting connections again, after accepting them keeps failing
+const MAX_ACCEPT_BACKOFF: Duration = Duration::from_secs(1);
+
+/// The commands of a REPL, along with its name, version and description: everything which is
+/// the same for every session. A registry holds functions making the commands rather than the
+/// commands themselves, so it can be shared between threads in an `Arc` even though commands
+/// and their handlers needn't be `Send` or `Sync`. Each session gets a
+/// [Repl](struct.Repl.html) of its own, holding its context and its own copy of the commands:
+/// ```
+/// use repl_rs::{Command, Parameter, Registry, Repl, Result, Value};
+/// use std::collections::HashMap;
+/// use std::sync::Arc;
+///
+/// // State shared by the whole application
+/// struct App {
+///     greeting: String,
+/// }
+///
+/// // State belonging to one session
+/// struct Session {
+///     app: Arc<App>,
+///     greeted: Vec<String>,
+/// }
+///
+/// fn hello(args: HashMap<String, Value>, session: &mut Session) -> Result<Option<String>> {
+///     session.greeted.push(args["who"].to_string());
+///     Ok(Some(format!("{}, {}", session.app.greeting, args["who"])))
+/// }
+///
+/// fn main() -> Result<()> {
+///     let app = Arc::new(App { greeting: "Hello".into() });
+///     let registry = Arc::new(Registry::new().with_name("greeter").add_command(|_, _| {
+///         Command::new("hello", hello)
+///             .with_parameter(Parameter::new("who").set_required(true).unwrap())
+///             .unwrap()
+///     }));
+///
+///     let sessions = (0..2).map(|_| {
+///         let registry = registry.clone();
+///         let app = app.clone();
+///         std::thread::spawn(move || {
+///             let session = Session { app, greeted: vec![] };
+///             let mut repl = Repl::with_registry(registry, session);
+///             repl.execute("hello world")
+///         })
+///     });
+///     for session in sessions.collect::<Vec<_>>() {
+///         assert_eq!(Ok(Some("Hello, world".into())), session.join().unwrap());
+///     }
+///
+///     Ok(())
+/// }
+/// ```
+pub struct Registry<Context, E> {
+    pub(crate) name: String,
+    pub(crate) version: String,
+    pub(crate) description: String,
+    pub(crate) commands: Vec<CommandFn<Context, E>>,
+    error_output: Mutex<Box<dyn Write + Send>>,
+}
+
+// Derived Default would need the context and error types to implement it too
+impl<Context, E> Default for Registry<Context, E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<Context, E> Registry<Context, E> {
+    /// Create a new, empty registry
+    pub fn new() -> Self {
+        Self {
+            name: String::new(),
+            version: String::new(),
+            description: String::new(),
+            commands: vec![],
+            error_output: Mutex::new(Box::new(io::stderr())),
+        }
+    }
+
+    /// Give the REPL a name. This is used in the help summary and the default prompt.
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = name.to_string();
+
+        self
+    }
+
+    /// Give the REPL a version. This is used in the help summary.
+    pub fn with_version(mut self, version: &str) -> Self {
+        self.version = version.to_string();
+
+        self
+    }
+
+    /// Give the REPL a description. This is used in the help summary.
+    pub fn with_description(mut self, description: &str) -> Self {
+        self.description = description.to_string();
+
+        self
+    }
+
+    /// Add a command to the registry, given a function making it. The function is called
+    /// once for each session, so every session has commands of its own, and only the function
+    /// has to be `Send` and `Sync`, not the command's handler. It's passed the session's
+    /// [ExitHandle](struct.ExitHandle.html) and
+    /// [CancellationToken](struct.CancellationToken.html), for the handler to capture.
+    pub fn add_command<F>(mut self, command: F) -> Self
+    where
+        F: 'static + Send + Sync + Fn(ExitHandle, CancellationToken) -> Command<Context, E>,
+    {
+        self.commands.push(Box::new(command));
+
+        self
+    }
+
+    /// Send errors accepting connections, and errors which end a session's thread, to the
+    /// given sink instead of stderr. Errors within a session go to the session's Repl.
+    pub fn with_error_output<W: 'static + Write + Send>(mut self, output: W) -> Self {
+        self.error_output = Mutex::new(Box::new(output));
+
+        self
+    }
+}
+
+impl<Context, E> Registry<Context, E>
+where
+    Context: 'static,
+    E: 'static + Display + From<Error>,
+{
+    /// Serve the registry's commands to clients connecting to the TCP listener, running each
+    /// connection's session on a thread of its own, so clients don't wait for each other. The
+    /// Repl for each session is made by calling `new_session` with the registry, on the
+    /// session's thread; this is where the session's context is created, for instance from
+    /// application state shared in an `Arc`, and where settings like the prompt are made. See
+    /// [Repl::run_session](struct.Repl.html#method.run_session) for how each session is run.
+    /// Errors accepting a connection are written to the
+    /// [error output](#method.with_error_output), waiting longer after each one that follows
+    /// another, and serving carries on, so this doesn't return while the listener is open.
+    pub fn serve_tcp<F>(self: Arc<Self>, listener: TcpListener, new_session: F) -> Result<()>
+    where
+        F: 'static + Send + Sync + Fn(Arc<Self>) -> Repl<Context, E>,
+    {
+        self.serve(listener.incoming(), new_session)
+    }
+
+    /// Serve the registry's commands to clients connecting to the Unix domain socket listener,
+    /// the same way as [serve_tcp](#method.serve_tcp)
+    #[cfg(unix)]
+    pub fn serve_unix<F>(self: Arc<Self>, listener: UnixListener, new_session: F) -> Result<()>
+    where
+        F: 'static + Send + Sync + Fn(Arc<Self>) -> Repl<Context, E>,
+    {
+        self.serve(listener.incoming(), new_session)
+    }
+
+    fn serve<S, F>(
+        self: Arc<Self>,
+        incoming: impl Iterator<Item = io::Result<S>>,
+        new_session: F,
+    ) -> Result<()>
+    where
+        S: Connection + Send,
+        F: 'static + Send + Sync + Fn(Arc<Self>) -> Repl<Context, E>,
+    {
+        let new_session = Arc::new(new_session);
+        let mut backoff = Duration::ZERO;
+        for connection in incoming {
+            let connection = match connection {
+                Ok(connection) => connection,
+                Err(error) => {
+                    self.report(format_args!("Error accepting connection: {}", error))?;
+                    // Errors like running out of file descriptors would otherwise spin
+                    backoff = (backoff * 2).clamp(Duration::from_millis(10), MAX_ACCEPT_BACKOFF);
+                    std::thread::sleep(backoff);
+                    continue;
+                }
+            };
+            backoff = Duration::ZERO;
+            let registry = self.clone();
+            let new_session = new_session.clone();
+            std::thread::spawn(move || {
+                let result = new_session(registry.clone()).serve_connection(connection);
+                if let Err(error) = result {
+                    // There's nothing left to report a failure to report to
+                    let _ = registry.report(format_args!("Session ended with error: {}", error));
+                }
+            });
+        }
+
+        Ok(())
+    }
+
+    fn report(&self, message: std::fmt::Arguments) -> Result<()> {
+        let mut output = self.error_output.lock().unwrap();
+        writeln!(output, "{}", message)?;
+
+        Ok(())
+    }
+}
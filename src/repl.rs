@@ -1,21 +1,73 @@
+use crate::alias::Aliases;
+use crate::cancel::{CancellationToken, InterruptHandler};
 use crate::error::*;
+use crate::exit::ExitHandle;
 use crate::help::{DefaultHelpViewer, HelpContext, HelpEntry, HelpViewer};
+use crate::helper::{self, CompletionNode, Helper, MatchStrategy};
+use crate::history::History;
+use crate::input;
+use crate::script::{ScriptFailure, ScriptSummary};
+use crate::suggest;
 use crate::Value;
-use crate::{Command, Parameter};
-use rustyline::completion;
-use rustyline_derive::{Helper, Highlighter, Hinter, Validator};
+use crate::{Command, Parameter, Registry};
 use std::boxed::Box;
 use std::collections::HashMap;
 use std::fmt::Display;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
 use yansi::Paint;
 
-type ErrorHandler<Context, E> = fn(error: E, repl: &Repl<Context, E>) -> Result<()>;
+type ExitHook<Context, E> = Box<dyn FnMut(&mut Context) -> core::result::Result<(), E>>;
+
+// Commands which the Repl handles itself, unless they're overridden
+const BUILTIN_COMMANDS: [&str; 4] = ["help", "exit", "quit", "alias"];
+
+type PromptFn<Context> = Box<dyn Fn(&Context) -> String>;
+
+// Connection accepted by a server, which is cloned so that a session can read from it and
+// write its output and errors to it
+pub(crate) trait Connection: 'static + Read + Write + Sized {
+    fn try_clone(&self) -> io::Result<Self>;
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()>;
+}
+
+impl Connection for TcpStream {
+    fn try_clone(&self) -> io::Result<Self> {
+        TcpStream::try_clone(self)
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        TcpStream::set_read_timeout(self, timeout)
+    }
+}
+
+#[cfg(unix)]
+impl Connection for UnixStream {
+    fn try_clone(&self) -> io::Result<Self> {
+        UnixStream::try_clone(self)
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        UnixStream::set_read_timeout(self, timeout)
+    }
+}
+
+type ErrorHandler<Context, E> =
+    fn(error: E, repl: &Repl<Context, E>, output: &mut dyn Write) -> Result<()>;
 
 fn default_error_handler<Context, E: std::fmt::Display>(
     error: E,
     _repl: &Repl<Context, E>,
+    output: &mut dyn Write,
 ) -> Result<()> {
-    eprintln!("{}", error);
+    writeln!(output, "{}", error)?;
     Ok(())
 }
 
@@ -25,13 +77,32 @@ pub struct Repl<Context, E: std::fmt::Display> {
     version: String,
     description: String,
     prompt: Box<dyn Display>,
+    prompt_fn: Option<PromptFn<Context>>,
     custom_prompt: bool,
     commands: HashMap<String, Command<Context, E>>,
-    context: Context,
+    aliases: Aliases,
+    // Only taken while a line is being read, when it's lent to the line editor's helper
+    context: Option<Context>,
     help_context: Option<HelpContext>,
     help_viewer: Box<dyn HelpViewer>,
     error_handler: ErrorHandler<Context, E>,
     use_completion: bool,
+    use_hints: bool,
+    use_highlighting: bool,
+    match_strategy: MatchStrategy,
+    stop_on_error: bool,
+    output: Box<dyn Write>,
+    error_output: Box<dyn Write>,
+    history: History,
+    exit: ExitHandle,
+    exit_hooks: Vec<ExitHook<Context, E>>,
+    // Set while run, run_async, run_session or run_reader is reading lines, since they call the
+    // exit hooks themselves when they stop
+    running: bool,
+    cancel: CancellationToken,
+    exit_on_double_interrupt: bool,
+    interrupted: bool,
+    session_timeout: Option<Duration>,
 }
 
 impl<Context, E> Repl<Context, E>
@@ -47,16 +118,49 @@ where
             version: String::new(),
             description: String::new(),
             prompt: Box::new(Paint::green(format!("{}> ", name)).bold()),
+            prompt_fn: None,
             custom_prompt: false,
             commands: HashMap::new(),
-            context,
+            aliases: Aliases::default(),
+            context: Some(context),
             help_context: None,
             help_viewer: Box::new(DefaultHelpViewer::new()),
             error_handler: default_error_handler,
             use_completion: false,
+            use_hints: false,
+            use_highlighting: false,
+            match_strategy: MatchStrategy::Prefix,
+            stop_on_error: true,
+            output: Box::new(io::stdout()),
+            error_output: Box::new(io::stderr()),
+            history: History::new(),
+            exit: ExitHandle::default(),
+            exit_hooks: vec![],
+            running: false,
+            cancel: CancellationToken::default(),
+            exit_on_double_interrupt: false,
+            interrupted: false,
+            session_timeout: None,
         }
     }
 
+    /// Create a new Repl for a session running the commands in the registry, with its own
+    /// context. The registry can be shared with other sessions, on other threads; each session
+    /// makes its own copy of the registry's commands, so anything added through this Repl's
+    /// builder methods only applies to this session.
+    pub fn with_registry(registry: Arc<Registry<Context, E>>, context: Context) -> Self {
+        let mut repl = Self::new(context)
+            .with_name(&registry.name)
+            .with_version(&registry.version)
+            .with_description(&registry.description);
+        for command in &registry.commands {
+            let command = command(repl.exit_handle(), repl.cancellation_token());
+            repl = repl.add_command(command);
+        }
+
+        repl
+    }
+
     /// Give your Repl a name. This is used in the help summary for the Repl.
     pub fn with_name(mut self, name: &str) -> Self {
         self.name = name.to_string();
@@ -85,6 +189,31 @@ where
     /// a `>`, all in green, followed by a space.
     pub fn with_prompt(mut self, prompt: &'static dyn Display) -> Self {
         self.prompt = Box::new(prompt);
+        self.prompt_fn = None;
+        self.custom_prompt = true;
+
+        self
+    }
+
+    /// Give your Repl a prompt which is computed from the context before each line is read, so
+    /// it can show things like the current directory or connection status. You can color parts
+    /// of it with [Paint](struct.Paint.html), and build it up from segments with
+    /// [Prompt](struct.Prompt.html):
+    /// ```
+    /// use repl_rs::{Paint, Repl};
+    ///
+    /// struct Context {
+    ///     database: Option<String>,
+    /// }
+    ///
+    /// let repl = Repl::<Context, repl_rs::Error>::new(Context { database: None })
+    ///     .with_prompt_fn(|context: &Context| match &context.database {
+    ///         Some(database) => format!("{}:{}> ", Paint::green("db"), Paint::cyan(database)),
+    ///         None => format!("{}> ", Paint::red("db")),
+    ///     });
+    /// ```
+    pub fn with_prompt_fn<F: 'static + Fn(&Context) -> String>(mut self, prompt: F) -> Self {
+        self.prompt_fn = Some(Box::new(prompt));
         self.custom_prompt = true;
 
         self
@@ -98,13 +227,35 @@ where
     }
 
     /// Pass in a custom error handler. This is really only for testing - the default
-    /// error handler simply prints the error to stderr and then returns
+    /// error handler simply writes the error to the error output and then returns
     pub fn with_error_handler(mut self, handler: ErrorHandler<Context, E>) -> Self {
         self.error_handler = handler;
 
         self
     }
 
+    /// Set how command names are matched when completing with tab. Defaults to
+    /// [MatchStrategy::Prefix](enum.MatchStrategy.html#variant.Prefix).
+    pub fn with_match_strategy(mut self, strategy: MatchStrategy) -> Self {
+        self.match_strategy = strategy;
+
+        self
+    }
+
+    /// Send command output, help and the welcome banner to the given sink instead of stdout
+    pub fn with_output<W: 'static + Write>(mut self, output: W) -> Self {
+        self.output = Box::new(output);
+
+        self
+    }
+
+    /// Send errors to the given sink instead of stderr. This is passed to the error handler.
+    pub fn with_error_output<W: 'static + Write>(mut self, output: W) -> Self {
+        self.error_output = Box::new(output);
+
+        self
+    }
+
     /// Set whether to use command completion when tab is hit. Defaults to false.
     pub fn use_completion(mut self, value: bool) -> Self {
         self.use_completion = value;
@@ -112,6 +263,113 @@ where
         self
     }
 
+    /// Set whether to show a greyed-out hint after the cursor with the parameters of the command
+    /// still to be typed. Defaults to false.
+    pub fn use_hints(mut self, value: bool) -> Self {
+        self.use_hints = value;
+
+        self
+    }
+
+    /// Set whether to color the line as it's typed: known commands in green, unknown ones in
+    /// red, and quoted strings in yellow. Defaults to false.
+    pub fn use_highlighting(mut self, value: bool) -> Self {
+        self.use_highlighting = value;
+
+        self
+    }
+
+    /// Keep the command history in the given file, so it persists across sessions. The history
+    /// is loaded when [run](#method.run) starts, saved every few commands, and saved again when
+    /// it returns.
+    pub fn with_history_file<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.history.file = Some(path.as_ref().to_path_buf());
+
+        self
+    }
+
+    /// Set the maximum number of lines kept in the history. Defaults to 100.
+    pub fn with_history_size(mut self, max_size: usize) -> Self {
+        self.history.max_size = max_size;
+
+        self
+    }
+
+    /// Set whether a line which is the same as the one before it is left out of the history.
+    /// Defaults to true.
+    pub fn history_ignore_dups(mut self, value: bool) -> Self {
+        self.history.ignore_dups = value;
+
+        self
+    }
+
+    /// Leave lines for which the predicate returns true out of the history, for instance
+    /// commands which contain passwords
+    pub fn exclude_from_history<F: 'static + Fn(&str) -> bool>(mut self, predicate: F) -> Self {
+        self.history.exclude = Some(Box::new(predicate));
+
+        self
+    }
+
+    /// Set whether running a script stops at the first line which fails. Defaults to true.
+    pub fn stop_on_error(mut self, value: bool) -> Self {
+        self.stop_on_error = value;
+
+        self
+    }
+
+    /// Add a hook which is called with the context when the Repl stops, so you can flush any
+    /// state. The hooks are called once each time it stops: when [run](#method.run),
+    /// [run_async](#method.run_async) or [run_session](#method.run_session) returns, when a
+    /// script run by [run_reader](#method.run_reader) asks to exit, and when a line run by
+    /// [execute](#method.execute) asks to exit. A script which reaches its end without asking
+    /// to exit, like one setting things up before `run`, doesn't call them. Hooks are called
+    /// in the order they were added, and any errors they return are passed to the error
+    /// handler.
+    pub fn on_exit<F>(mut self, hook: F) -> Self
+    where
+        F: 'static + FnMut(&mut Context) -> core::result::Result<(), E>,
+    {
+        self.exit_hooks.push(Box::new(hook));
+
+        self
+    }
+
+    /// Get a handle which your commands can use to make the Repl exit
+    pub fn exit_handle(&self) -> ExitHandle {
+        self.exit.clone()
+    }
+
+    /// Get a token which your commands can check to see whether the user has pressed Ctrl-C
+    /// while they're running
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.cancel.clone()
+    }
+
+    /// Set whether pressing Ctrl-C twice in a row at the prompt exits the Repl. Either way, a
+    /// single Ctrl-C clears the current line. Defaults to false.
+    pub fn exit_on_double_interrupt(mut self, value: bool) -> Self {
+        self.exit_on_double_interrupt = value;
+
+        self
+    }
+
+    /// End a session served over a socket when its client sends nothing for the given time, so
+    /// an idle client can't hold on to the Repl. By default sessions wait for their clients
+    /// forever.
+    pub fn with_session_timeout(mut self, timeout: Duration) -> Self {
+        self.session_timeout = Some(timeout);
+
+        self
+    }
+
+    /// Whether the Repl has been asked to exit, either by the built-in `exit` or `quit` commands
+    /// or through an [ExitHandle](struct.ExitHandle.html). Useful if you're calling
+    /// [execute](#method.execute) yourself.
+    pub fn is_exit_requested(&self) -> bool {
+        self.exit.is_exit_requested()
+    }
+
     /// Add a command to your REPL
     pub fn add_command(mut self, command: Command<Context, E>) -> Self {
         self.commands.insert(command.name.clone(), command);
@@ -120,107 +378,482 @@ where
     }
 
     fn validate_arguments(
-        &self,
         command: &str,
         parameters: &[Parameter],
         args: &[&str],
     ) -> Result<HashMap<String, Value>> {
-        if args.len() > parameters.len() {
-            return Err(Error::TooManyArguments(command.into(), parameters.len()));
+        let (options, positionals) = Self::parse_options(command, parameters, args)?;
+        let positional_parameters = parameters
+            .iter()
+            .filter(|parameter| parameter.is_positional())
+            .collect::<Vec<&Parameter>>();
+        let fixed = positional_parameters
+            .iter()
+            .filter(|parameter| parameter.variadic.is_none())
+            .count();
+        let max = match positional_parameters.last().and_then(|p| p.variadic) {
+            Some((_, Some(max))) => Some(fixed + max),
+            Some((_, None)) => None,
+            None => Some(fixed),
+        };
+        if let Some(max) = max {
+            if positionals.len() > max {
+                return Err(Error::TooManyArguments(command.into(), max));
+            }
         }
 
         let mut validated = HashMap::new();
-        for (index, parameter) in parameters.iter().enumerate() {
-            if index < args.len() {
-                validated.insert(parameter.name.clone(), Value::new(args[index]));
+        for (index, parameter) in positional_parameters.iter().enumerate() {
+            if let Some((min, _)) = parameter.variadic {
+                let rest = positionals.get(index..).unwrap_or(&[]);
+                if rest.is_empty() && parameter.required {
+                    return Err(Error::MissingRequiredArgument(
+                        command.into(),
+                        parameter.name.clone(),
+                    ));
+                } else if rest.len() < min {
+                    return Err(Error::TooFewArguments(
+                        command.into(),
+                        parameter.name.clone(),
+                        min,
+                    ));
+                }
+                match &parameter.default {
+                    Some(default) if rest.is_empty() => {
+                        validated.insert(parameter.name.clone(), Value::new(default));
+                    }
+                    _ => {
+                        for arg in rest {
+                            parameter.validate(arg)?;
+                        }
+                        let values = rest.iter().map(|arg| arg.to_string()).collect();
+                        validated.insert(parameter.name.clone(), Value::new_list(values));
+                    }
+                }
+            } else if index < positionals.len() {
+                parameter.validate(positionals[index])?;
+                validated.insert(parameter.name.clone(), Value::new(positionals[index]));
             } else if parameter.required {
                 return Err(Error::MissingRequiredArgument(
                     command.into(),
                     parameter.name.clone(),
                 ));
-            } else if parameter.default.is_some() {
-                validated.insert(
-                    parameter.name.clone(),
-                    Value::new(&parameter.default.clone().unwrap()),
-                );
+            } else if let Some(default) = &parameter.default {
+                validated.insert(parameter.name.clone(), Value::new(default));
+            }
+        }
+        for parameter in parameters
+            .iter()
+            .filter(|parameter| !parameter.is_positional())
+        {
+            match options.get(&parameter.name) {
+                Some(values) if parameter.multiple => {
+                    validated.insert(parameter.name.clone(), Value::new_list(values.clone()));
+                }
+                Some(values) => {
+                    validated.insert(parameter.name.clone(), Value::new(values.last().unwrap()));
+                }
+                None if parameter.flag => {
+                    validated.insert(parameter.name.clone(), Value::new("false"));
+                }
+                None if parameter.required => {
+                    return Err(Error::MissingRequiredArgument(
+                        command.into(),
+                        parameter.name.clone(),
+                    ));
+                }
+                None => {
+                    if let Some(default) = &parameter.default {
+                        validated.insert(parameter.name.clone(), Value::new(default));
+                    }
+                }
             }
         }
         Ok(validated)
     }
 
-    fn handle_command(&mut self, command: &str, args: &[&str]) -> core::result::Result<(), E> {
-        match self.commands.get(command) {
-            Some(definition) => {
-                let validated = self.validate_arguments(command, &definition.parameters, args)?;
-                match (definition.callback)(validated, &mut self.context) {
-                    Ok(Some(value)) => println!("{}", value),
-                    Ok(None) => (),
-                    Err(error) => return Err(error),
+    // Pull the named options out of the arguments, returning the values given for each option
+    // (keyed by parameter name) and the remaining positional arguments
+    #[allow(clippy::type_complexity)]
+    fn parse_options<'a>(
+        command: &str,
+        parameters: &[Parameter],
+        args: &[&'a str],
+    ) -> Result<(HashMap<String, Vec<String>>, Vec<&'a str>)> {
+        let mut options: HashMap<String, Vec<String>> = HashMap::new();
+        let mut positionals = vec![];
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            if *arg == "--" {
+                positionals.extend(args);
+                break;
+            } else if let Some(long) = arg.strip_prefix("--") {
+                let (name, inline_value) = match long.split_once('=') {
+                    Some((name, value)) => (name, Some(value.to_string())),
+                    None => (long, None),
                 };
+                let parameter = parameters
+                    .iter()
+                    .find(|parameter| parameter.long.as_deref() == Some(name))
+                    .ok_or_else(|| Error::UnknownOption(command.into(), arg.to_string()))?;
+                let value = match inline_value {
+                    Some(value) => value,
+                    None if parameter.flag => "true".to_string(),
+                    None => args.next().map(|value| value.to_string()).ok_or_else(|| {
+                        Error::MissingOptionValue(command.into(), arg.to_string())
+                    })?,
+                };
+                parameter.validate(&value)?;
+                options
+                    .entry(parameter.name.clone())
+                    .or_default()
+                    .push(value);
+            } else if arg.len() > 1 && arg.starts_with('-') && arg.parse::<f64>().is_err() {
+                // One or more short options, the last of which can take a value, either
+                // attached (`-n5`) or as the next argument (`-n 5`)
+                let shorts = &arg[1..];
+                for (index, short) in shorts.char_indices() {
+                    let parameter = parameters
+                        .iter()
+                        .find(|parameter| parameter.short == Some(short))
+                        .ok_or_else(|| {
+                            Error::UnknownOption(command.into(), format!("-{}", short))
+                        })?;
+                    if parameter.flag {
+                        options
+                            .entry(parameter.name.clone())
+                            .or_default()
+                            .push("true".to_string());
+                        continue;
+                    }
+                    let rest = &shorts[index + short.len_utf8()..];
+                    let value = if !rest.is_empty() {
+                        rest.to_string()
+                    } else {
+                        args.next().map(|value| value.to_string()).ok_or_else(|| {
+                            Error::MissingOptionValue(command.into(), format!("-{}", short))
+                        })?
+                    };
+                    parameter.validate(&value)?;
+                    options
+                        .entry(parameter.name.clone())
+                        .or_default()
+                        .push(value);
+                    break;
+                }
+            } else {
+                positionals.push(*arg);
             }
-            None => {
-                if command == "help" {
-                    self.show_help(args)?;
-                } else {
-                    return Err(Error::UnknownCommand(command.to_string()).into());
+        }
+
+        Ok((options, positionals))
+    }
+
+    fn handle_command(
+        &mut self,
+        command: &str,
+        args: &[&str],
+        body: Option<String>,
+    ) -> core::result::Result<Option<String>, E> {
+        let name = self.command_name(command);
+        match self.commands.get_mut(&name) {
+            Some(definition) => {
+                if let Some(handler) = &mut definition.raw_handler {
+                    return handler(args, self.context.as_mut().unwrap());
+                }
+                let (definition, depth) = definition.resolve(args);
+                let path = Self::command_path(command, args, depth);
+                let args = &args[depth..];
+                let handler = match &mut definition.handler {
+                    Some(handler) => handler,
+                    #[cfg(feature = "async")]
+                    None if definition.async_handler.is_some() => {
+                        return Err(Error::AsyncCommand(path).into())
+                    }
+                    None if args.is_empty() => return Err(Error::MissingSubcommand(path).into()),
+                    None => {
+                        let suggestions =
+                            suggest::suggestions(args[0], definition.subcommand_names());
+                        let command = format!("{} {}", path, args[0]);
+                        return Err(Error::UnknownCommand(command, suggestions).into());
+                    }
+                };
+                let mut validated = Self::validate_arguments(&path, &definition.parameters, args)?;
+                if let (Some(name), Some(body)) = (&definition.body, body) {
+                    validated.insert(name.clone(), Value::new(&body));
                 }
+                handler.handle(validated, self.context.as_mut().unwrap())
             }
+            None => match command {
+                "help" => Ok(self.show_help(args)?),
+                "exit" | "quit" => {
+                    self.exit.request_exit();
+                    Ok(None)
+                }
+                "alias" => Ok(self.define_alias(args)?),
+                _ => Err(self.unknown_command(command).into()),
+            },
         }
+    }
 
-        Ok(())
+    // Like handle_command, but awaits the command if it's async
+    #[cfg(feature = "async")]
+    async fn handle_command_async(
+        &mut self,
+        command: &str,
+        args: &[&str],
+        body: Option<String>,
+    ) -> core::result::Result<Option<String>, E> {
+        let name = self.command_name(command);
+        let (definition, depth) = match self.commands.get_mut(&name) {
+            Some(definition) if definition.raw_handler.is_none() => definition.resolve(args),
+            _ => return self.handle_command(command, args, body),
+        };
+        if definition.async_handler.is_none() {
+            return self.handle_command(command, args, body);
+        }
+        let path = Self::command_path(command, args, depth);
+        let mut validated =
+            Self::validate_arguments(&path, &definition.parameters, &args[depth..])?;
+        if let (Some(name), Some(body)) = (&definition.body, body) {
+            validated.insert(name.clone(), Value::new(&body));
+        }
+        let handler = definition.async_handler.as_mut().unwrap();
+        // The command's future borrows the context until it's done
+        handler(validated, self.context.as_mut().unwrap()).await
+    }
+
+    // Name of the command with the given name or alias, or the name itself if there isn't one
+    fn command_name(&self, command: &str) -> String {
+        match self.commands.contains_key(command) {
+            true => command.to_string(),
+            false => self
+                .commands
+                .values()
+                .find(|definition| definition.is_called(command))
+                .map_or_else(|| command.to_string(), |definition| definition.name.clone()),
+        }
+    }
+
+    // The command as typed, down to the subcommand the arguments led to
+    fn command_path(command: &str, args: &[&str], depth: usize) -> String {
+        std::iter::once(command)
+            .chain(args[..depth].iter().copied())
+            .collect::<Vec<&str>>()
+            .join(" ")
+    }
+
+    // Whether the command named by the arguments takes a body after them
+    fn takes_body(&self, args: &[String]) -> bool {
+        let args = self.aliases.expand(args.to_vec());
+        let args = args.iter().map(|arg| arg.as_str()).collect::<Vec<&str>>();
+        match args.split_first() {
+            Some((command, args)) => self
+                .commands
+                .values()
+                .find(|definition| definition.is_called(command))
+                .is_some_and(|definition| definition.takes_body(args)),
+            None => false,
+        }
+    }
+
+    // Whether the name is taken by a command, one of their aliases, or a built-in command
+    fn is_command(&self, name: &str) -> bool {
+        BUILTIN_COMMANDS.contains(&name)
+            || self
+                .commands
+                .values()
+                .any(|definition| definition.is_called(name))
+    }
+
+    // Error for a command that doesn't exist, suggesting any with similar names
+    fn unknown_command(&self, command: &str) -> Error {
+        let aliases = self.aliases.names();
+        let names = self
+            .commands
+            .values()
+            .flat_map(|definition| {
+                std::iter::once(definition.name.as_str())
+                    .chain(definition.aliases.iter().map(String::as_str))
+            })
+            .chain(aliases.iter().map(String::as_str))
+            .chain(BUILTIN_COMMANDS);
+        let suggestions = suggest::suggestions(command, names);
+        Error::UnknownCommand(command.to_string(), suggestions)
     }
 
-    fn show_help(&self, args: &[&str]) -> Result<()> {
+    // The built-in `alias` command. With no arguments it lists the aliases, otherwise it makes
+    // the first argument an alias for the command and any arguments after it.
+    fn define_alias(&mut self, args: &[&str]) -> Result<Option<String>> {
+        match args {
+            [] => {
+                let aliases = self
+                    .aliases
+                    .describe()
+                    .into_iter()
+                    .map(|(alias, expansion)| format!("{} = {}", alias, expansion))
+                    .collect::<Vec<String>>();
+                Ok((!aliases.is_empty()).then(|| aliases.join("\n")))
+            }
+            [_] => Err(Error::MissingRequiredArgument(
+                "alias".into(),
+                "command".into(),
+            )),
+            [alias, expansion @ ..] => {
+                if self.is_command(alias) {
+                    return Err(Error::IllegalAliasError(alias.to_string()));
+                }
+                let expansion = self
+                    .aliases
+                    .expand(expansion.iter().map(|arg| arg.to_string()).collect());
+                if !self.is_command(&expansion[0]) {
+                    return Err(self.unknown_command(&expansion[0]));
+                }
+                self.aliases.insert(alias, expansion);
+                self.construct_help_context();
+                Ok(None)
+            }
+        }
+    }
+
+    // Render the help into a string, so it's returned from execute like a command's output
+    fn show_help(&mut self, args: &[&str]) -> Result<Option<String>> {
+        let mut help = vec![];
         if args.is_empty() {
             self.help_viewer
-                .help_general(self.help_context.as_ref().unwrap())?;
+                .help_general(self.help_context.as_ref().unwrap(), &mut help)?;
         } else {
-            let entry_opt = self
-                .help_context
-                .as_ref()
-                .unwrap()
-                .help_entries
-                .iter()
-                .find(|entry| entry.command == args[0]);
+            // Help for an alias is the help for the command it expands to, leaving out any
+            // arguments it gives the command
+            let preset = self
+                .aliases
+                .get(args[0])
+                .map_or(0, |expansion| expansion.len());
+            let words = self
+                .aliases
+                .expand(args.iter().map(|arg| arg.to_string()).collect());
+            let mut entries = &self.help_context.as_ref().unwrap().help_entries;
+            let mut entry_opt = None;
+            for (index, word) in words.iter().enumerate() {
+                let found = entries.iter().find(|entry| {
+                    entry.command.rsplit(' ').next() == Some(word.as_str())
+                        || entry.aliases.contains(word)
+                });
+                match found {
+                    Some(entry) => {
+                        entry_opt = Some(entry);
+                        entries = &entry.subcommands;
+                    }
+                    None if index < preset => break,
+                    None => {
+                        entry_opt = None;
+                        break;
+                    }
+                }
+            }
             match entry_opt {
                 Some(entry) => {
-                    self.help_viewer.help_command(entry)?;
+                    self.help_viewer.help_command(entry, &mut help)?;
                 }
-                None => eprintln!("Help not found for command '{}'", args[0]),
+                None => return Err(self.unknown_command(&args.join(" "))),
             };
         }
-        Ok(())
+        let help = String::from_utf8_lossy(&help);
+        Ok(Some(help.trim_end_matches('\n').to_string()).filter(|help| !help.is_empty()))
+    }
+
+    /// Run a single line through the parser and the command dispatcher, without a terminal,
+    /// returning whatever the command returned, or the help for the built-in `help`. A blank
+    /// line does nothing and returns `Ok(None)`. The line can hold several lines joined with
+    /// newlines, for instance a command followed by its
+    /// [body](struct.Command.html#method.with_body).
+    pub fn execute(&mut self, line: &str) -> core::result::Result<Option<String>, E> {
+        let (args, body) = match self.parse_line(line)? {
+            Some(parsed) => parsed,
+            None => return Ok(None),
+        };
+        let args = args.iter().map(|a| a.as_str()).collect::<Vec<&str>>();
+        self.cancel.reset();
+        let exiting = self.exit.is_exit_requested();
+        let result = self.handle_command(args[0], &args[1..], body);
+        self.exited(exiting)?;
+        result
+    }
+
+    /// Like [execute](#method.execute), but async commands are awaited rather than rejected.
+    /// If the [cancellation token](#method.cancellation_token) is cancelled before the
+    /// command is done, the command's future is dropped and an
+    /// [Error::Cancelled](enum.Error.html#variant.Cancelled) is returned.
+    #[cfg(feature = "async")]
+    pub async fn execute_async(&mut self, line: &str) -> core::result::Result<Option<String>, E> {
+        let (args, body) = match self.parse_line(line)? {
+            Some(parsed) => parsed,
+            None => return Ok(None),
+        };
+        let args = args.iter().map(|a| a.as_str()).collect::<Vec<&str>>();
+        self.cancel.reset();
+        let cancel = self.cancel.clone();
+        let exiting = self.exit.is_exit_requested();
+        let result = tokio::select! {
+            result = self.handle_command_async(args[0], &args[1..], body) => result,
+            _ = cancel.cancelled() => Err(Error::Cancelled.into()),
+        };
+        self.exited(exiting)?;
+        result
+    }
+
+    // Called after execute runs a line: if the line asked the Repl to exit, and nothing's
+    // running it which will call the exit hooks as it stops, they're called now. The exit
+    // request is left for the caller to see.
+    fn exited(&mut self, exiting: bool) -> Result<()> {
+        match !self.running && !exiting && self.exit.is_exit_requested() {
+            true => self.call_exit_hooks(),
+            false => Ok(()),
+        }
+    }
+
+    // Split the line into the arguments, with any aliases expanded, and the body if there is
+    // one. Returns None for a blank line.
+    fn parse_line(&mut self, line: &str) -> Result<Option<(Vec<String>, Option<String>)>> {
+        if self.help_context.is_none() {
+            self.construct_help_context();
+        }
+        let input = input::parse(line, |args| self.takes_body(args))?;
+        let args = self.aliases.expand(input.args);
+        match args.is_empty() {
+            true => Ok(None),
+            false => Ok(Some((args, input.body))),
+        }
     }
 
     fn process_line(&mut self, line: String) -> core::result::Result<(), E> {
-        let trimmed = line.trim();
-        if !trimmed.is_empty() {
-            let r = regex::Regex::new(r#"("[^"\n]+"|[\S]+)"#).unwrap();
-            let args = r
-                .captures_iter(trimmed)
-                .map(|a| a[0].to_string().replace('\"', ""))
-                .collect::<Vec<String>>();
-            let mut args = args.iter().fold(vec![], |mut state, a| {
-                state.push(a.as_str());
-                state
-            });
-            let command: String = args.drain(..1).collect();
-            self.handle_command(&command, &args)?;
+        if let Some(value) = self.execute(&line)? {
+            writeln!(self.output, "{}", value).map_err(Error::from)?;
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "async")]
+    async fn process_line_async(&mut self, line: String) -> core::result::Result<(), E> {
+        if let Some(value) = self.execute_async(&line).await? {
+            writeln!(self.output, "{}", value).map_err(Error::from)?;
         }
         Ok(())
     }
 
+    // Pass an error to the error handler, along with the error output
+    fn handle_error(&mut self, error: E) -> Result<()> {
+        let mut output = std::mem::replace(&mut self.error_output, Box::new(io::sink()));
+        let result = (self.error_handler)(error, self, &mut *output);
+        self.error_output = output;
+        result
+    }
+
     fn construct_help_context(&mut self) {
         let mut help_entries = self
             .commands
             .values()
-            .map(|definition| {
-                HelpEntry::new(
-                    &definition.name,
-                    &definition.parameters,
-                    &definition.help_summary,
-                )
-            })
+            .map(|definition| HelpEntry::new(&definition.name, definition))
             .collect::<Vec<HelpEntry>>();
         help_entries.sort_by_key(|d| d.command.clone());
         self.help_context = Some(HelpContext::new(
@@ -228,126 +861,419 @@ where
             &self.version,
             &self.description,
             help_entries,
+            self.aliases.describe(),
         ));
     }
 
-    fn create_helper(&mut self) -> Helper {
-        let mut helper = Helper::new();
-        if self.use_completion {
-            for name in self.commands.keys() {
-                helper.add_command(name.to_string());
+    fn create_helper(&mut self) -> Helper<Context> {
+        let mut helper = Helper::new(self.match_strategy, self.aliases.clone());
+        helper.completion = self.use_completion;
+        helper.hints = self.use_hints;
+        helper.highlighting = self.use_highlighting;
+        for command in self.commands.values() {
+            helper.add_command(CompletionNode::new(command));
+        }
+        for name in BUILTIN_COMMANDS {
+            if !self.commands.contains_key(name) {
+                helper.add_command(CompletionNode::builtin(name));
             }
         }
 
         helper
     }
 
+    /// Run the Repl, reading lines from the terminal until end of file (Ctrl-D), or until it's
+    /// asked to exit with `exit`, `quit` or an [ExitHandle](struct.ExitHandle.html). On Unix,
+    /// pressing Ctrl-C while a command is running cancels its
+    /// [CancellationToken](struct.CancellationToken.html) rather than killing the process. This
+    /// SIGINT handler is only installed while `run` lasts, and not at all if the application
+    /// has set one of its own.
     pub fn run(&mut self) -> Result<()> {
+        let mut editor = self.start()?;
+        let interrupts = InterruptHandler::install(&self.cancel);
+        self.running = true;
+        let mut eof = false;
+        let mut result = Ok(());
+        while result.is_ok() && !eof && !self.exit.is_exit_requested() {
+            result = self.handle_line(&mut editor, &mut eof);
+        }
+        drop(interrupts);
+        self.running = false;
+
+        // The history is saved and the exit hooks called even if reading a line failed
+        let saved = self.history.save(&mut editor);
+        let finished = self.finish();
+        result.and(saved).and(finished)
+    }
+
+    /// Run the Repl like [run](#method.run), but awaiting async commands. This needs the
+    /// `async` feature, and has to be called from within tokio's multi-threaded runtime. The
+    /// line editor runs on a blocking thread, so other tasks carry on while it waits for input;
+    /// on the current-thread runtime it would block every other task, so an
+    /// [Error::CurrentThreadRuntime](enum.Error.html#variant.CurrentThreadRuntime) is returned
+    /// instead. Pressing Ctrl-C while a command is running drops its future, as well as
+    /// cancelling its [CancellationToken](struct.CancellationToken.html).
+    #[cfg(feature = "async")]
+    pub async fn run_async(&mut self) -> Result<()> {
+        let flavor = tokio::runtime::Handle::current().runtime_flavor();
+        if flavor == tokio::runtime::RuntimeFlavor::CurrentThread {
+            return Err(Error::CurrentThreadRuntime);
+        }
+        let mut editor = self.start()?;
+        let interrupts = InterruptHandler::install(&self.cancel);
+        self.running = true;
+        let mut eof = false;
+        let mut result = Ok(());
+        while result.is_ok() && !eof && !self.exit.is_exit_requested() {
+            result = self.handle_line_async(&mut editor, &mut eof).await;
+        }
+        drop(interrupts);
+        self.running = false;
+
+        // The history is saved and the exit hooks called even if reading a line failed
+        let saved = self.history.save(&mut editor);
+        let finished = self.finish();
+        result.and(saved).and(finished)
+    }
+
+    // Called when the session starts: sets up the line editor and greets the user
+    fn start(&mut self) -> Result<rustyline::Editor<Helper<Context>>> {
         self.construct_help_context();
-        let mut editor: rustyline::Editor<Helper> = rustyline::Editor::new();
+        let mut editor: rustyline::Editor<Helper<Context>> =
+            rustyline::Editor::with_config(self.history.config());
         let helper = Some(self.create_helper());
         editor.set_helper(helper);
-        println!("Welcome to {} {}", self.name, self.version);
-        let mut eof = false;
-        while !eof {
-            self.handle_line(&mut editor, &mut eof)?;
+        self.history.load(&mut editor)?;
+        writeln!(self.output, "Welcome to {} {}", self.name, self.version)?;
+
+        Ok(editor)
+    }
+
+    // Called when the session ends: calls the exit hooks, and resets the exit request so the
+    // Repl can be run again
+    fn finish(&mut self) -> Result<()> {
+        self.exit.reset();
+        self.call_exit_hooks()
+    }
+
+    fn call_exit_hooks(&mut self) -> Result<()> {
+        let mut hooks = std::mem::take(&mut self.exit_hooks);
+        let mut result = Ok(());
+        for hook in hooks.iter_mut() {
+            let hook_result = hook(self.context.as_mut().unwrap());
+            if let Err(error) = hook_result {
+                result = self.handle_error(error);
+                if result.is_err() {
+                    break;
+                }
+            }
         }
+        self.exit_hooks = hooks;
 
-        Ok(())
+        result
     }
 
-    fn handle_line(
-        &mut self,
-        editor: &mut rustyline::Editor<Helper>,
-        eof: &mut bool,
-    ) -> Result<()> {
-        match editor.readline(&format!("{}", self.prompt)) {
-            Ok(line) => {
-                editor.add_history_entry(line.clone());
-                if let Err(error) = self.process_line(line) {
-                    (self.error_handler)(error, self)?;
+    /// Run the commands in the given script file, without any line editing. See
+    /// [run_reader](#method.run_reader).
+    pub fn run_script<P: AsRef<Path>>(&mut self, path: P) -> Result<ScriptSummary> {
+        let file = File::open(path)?;
+        self.run_reader(BufReader::new(file))
+    }
+
+    /// Run the commands read from the given reader (for instance, `stdin().lock()`), one per
+    /// line, without any line editing. Blank lines, and lines starting with `#`, are skipped,
+    /// and `exit` or `quit` ends the script early. A command continues onto the next line if
+    /// a quote is left open, the line ends with a backslash, or it's followed by a body.
+    /// Errors are passed to the error handler as usual, and reading stops at the first error
+    /// unless [stop_on_error(false)](#method.stop_on_error) has been set. The
+    /// [exit hooks](#method.on_exit) are only called if the script asks to exit. Returns a
+    /// summary of the lines which failed.
+    pub fn run_reader<R: BufRead>(&mut self, reader: R) -> Result<ScriptSummary> {
+        self.construct_help_context();
+        let mut summary = ScriptSummary::default();
+        self.running = true;
+        let result = self.read_script(reader, &mut summary);
+        self.running = false;
+        // The exit hooks are called even if the script failed after asking to exit
+        let finished = match self.exit.is_exit_requested() {
+            true => self.finish(),
+            false => Ok(()),
+        };
+        result.and(finished)?;
+
+        Ok(summary)
+    }
+
+    fn read_script<R: BufRead>(&mut self, reader: R, summary: &mut ScriptSummary) -> Result<()> {
+        let mut lines = reader.lines().enumerate();
+        while let Some((index, line)) = lines.next() {
+            let mut line = line?;
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+            while !input::is_complete(&line, |args| self.takes_body(args)) {
+                match lines.next() {
+                    Some((_, next)) => {
+                        line.push('\n');
+                        line.push_str(&next?);
+                    }
+                    None => break,
                 }
-                *eof = false;
-                Ok(())
             }
-            Err(rustyline::error::ReadlineError::Eof) => {
-                *eof = true;
-                Ok(())
+            summary.executed += 1;
+            if let Err(error) = self.process_line(line.clone()) {
+                summary.failures.push(ScriptFailure {
+                    line_number: index + 1,
+                    line,
+                    message: error.to_string(),
+                });
+                self.handle_error(error)?;
+                if self.stop_on_error {
+                    break;
+                }
             }
-            Err(error) => {
-                eprintln!("Error reading line: {}", error);
-                *eof = false;
-                Ok(())
+            if self.exit.is_exit_requested() {
+                break;
             }
         }
-    }
-}
 
-// rustyline Helper struct
-// Currently just does command completion with <tab>, if
-// use_completion() is set on the REPL
-#[derive(Clone, Helper, Hinter, Highlighter, Validator)]
-struct Helper {
-    commands: Vec<String>,
-}
+        Ok(())
+    }
 
-impl Helper {
-    fn new() -> Self {
-        Self { commands: vec![] }
+    /// Serve the Repl to clients connecting to the TCP listener, for instance to give a
+    /// running daemon an admin console. See [run_session](#method.run_session) for how each
+    /// connection is handled.
+    ///
+    /// Only one client is served at a time: the others wait, in the order they connected, until
+    /// its session ends, so set a [session timeout](#method.with_session_timeout) if a client
+    /// might be left idle. To serve several clients at once, each with its own context, use
+    /// [Registry::serve_tcp](struct.Registry.html#method.serve_tcp) instead. Errors accepting a
+    /// connection are written to the error output, and serving carries on.
+    pub fn serve_tcp(&mut self, listener: TcpListener) -> Result<()> {
+        self.serve(listener.incoming())
     }
 
-    fn add_command(&mut self, command: String) {
-        self.commands.push(command);
+    /// Serve the Repl to clients connecting to the Unix domain socket listener, the same way
+    /// as [serve_tcp](#method.serve_tcp), one client at a time
+    #[cfg(unix)]
+    pub fn serve_unix(&mut self, listener: UnixListener) -> Result<()> {
+        self.serve(listener.incoming())
     }
-}
 
-impl completion::Completer for Helper {
-    type Candidate = String;
-
-    fn complete(
-        &self,
-        line: &str,
-        _pos: usize,
-        _ctx: &rustyline::Context<'_>,
-    ) -> rustyline::Result<(usize, Vec<Self::Candidate>)> {
-        // Complete based on whether the current line is a substring
-        // of one of the set commands
-        let ret: Vec<Self::Candidate> = self
-            .commands
-            .iter()
-            .filter(|cmd| cmd.contains(line))
-            .map(|s| s.to_string())
-            .collect();
-        Ok((0, ret))
+    fn serve<S: Connection>(
+        &mut self,
+        incoming: impl Iterator<Item = io::Result<S>>,
+    ) -> Result<()> {
+        self.construct_help_context();
+        for connection in incoming {
+            match connection {
+                Ok(connection) => self.serve_connection(connection)?,
+                Err(error) => writeln!(self.error_output, "Error accepting connection: {}", error)?,
+            }
+        }
+
+        Ok(())
     }
-}
 
-#[cfg(all(test, unix))]
-mod tests {
-    use crate::error::*;
-    use crate::repl::{Helper, Repl};
-    use crate::{initialize_repl, Value};
-    use crate::{Command, Parameter};
-    use clap::{crate_description, crate_name, crate_version};
-    use nix::sys::wait::{waitpid, WaitStatus};
-    use nix::unistd::{close, dup2, fork, pipe, ForkResult};
-    use std::collections::HashMap;
-    use std::fs::File;
-    use std::io::Write;
-    use std::os::unix::io::FromRawFd;
+    // Run a session for the connection. A client going away only ends its own session, so
+    // errors reading from or writing to it are reported rather than returned.
+    pub(crate) fn serve_connection<S: Connection>(&mut self, connection: S) -> Result<()> {
+        let result = connection.set_read_timeout(self.session_timeout);
+        let result = result
+            .and_then(|()| connection.try_clone())
+            .and_then(|output| {
+                let error_output = connection.try_clone()?;
+                Ok((output, error_output))
+            });
+        let result = match result {
+            Ok((output, error_output)) => {
+                self.run_session(BufReader::new(connection), output, error_output)
+            }
+            Err(error) => Err(error.into()),
+        };
+        if let Err(error) = result {
+            writeln!(self.error_output, "Session ended with error: {}", error)?;
+        }
 
-    fn test_error_handler<Context>(error: Error, _repl: &Repl<Context, Error>) -> Result<()> {
-        Err(error)
+        Ok(())
     }
 
-    fn foo<T>(args: HashMap<String, Value>, _context: &mut T) -> Result<Option<String>> {
-        Ok(Some(format!("foo {:?}", args)))
+    /// Run a session reading commands from the reader, and writing their output and errors to
+    /// the given writers instead of the Repl's usual ones, using a plain line protocol with no
+    /// line editing. The session is greeted, and the prompt is written before each command is
+    /// read. Errors are passed to the error handler, and don't end the session, which ends at
+    /// the end of the input or when it's asked to exit. Either way, the
+    /// [exit hooks](#method.on_exit) are called as it ends.
+    pub fn run_session<R, W, V>(&mut self, reader: R, output: W, error_output: V) -> Result<()>
+    where
+        R: BufRead,
+        W: 'static + Write,
+        V: 'static + Write,
+    {
+        let output = std::mem::replace(&mut self.output, Box::new(output));
+        let error_output = std::mem::replace(&mut self.error_output, Box::new(error_output));
+        self.running = true;
+        let result = self.session(reader);
+        self.running = false;
+        // Any errors from the hooks go to the session's error output
+        let finished = self.finish();
+        self.output = output;
+        self.error_output = error_output;
+
+        result.and(finished)
     }
 
-    fn run_repl<Context>(mut repl: Repl<Context, Error>, input: &str, expected: Result<()>) {
-        let (rdr, wrtr) = pipe().unwrap();
-        unsafe {
-            match fork() {
+    fn session<R: BufRead>(&mut self, reader: R) -> Result<()> {
+        if self.help_context.is_none() {
+            self.construct_help_context();
+        }
+        writeln!(self.output, "Welcome to {} {}", self.name, self.version)?;
+        let mut lines = reader.lines();
+        while !self.exit.is_exit_requested() {
+            write!(self.output, "{}", self.current_prompt())?;
+            self.output.flush()?;
+            let mut line = match lines.next() {
+                Some(line) => line?,
+                None => break,
+            };
+            while !input::is_complete(&line, |args| self.takes_body(args)) {
+                match lines.next() {
+                    Some(next) => {
+                        line.push('\n');
+                        line.push_str(&next?);
+                    }
+                    None => break,
+                }
+            }
+            if let Err(error) = self.process_line(line) {
+                self.handle_error(error)?;
+            }
+            self.output.flush()?;
+        }
+
+        Ok(())
+    }
+
+    fn current_prompt(&self) -> String {
+        match &self.prompt_fn {
+            Some(prompt) => prompt(self.context.as_ref().unwrap()),
+            None => self.prompt.to_string(),
+        }
+    }
+
+    // Ctrl-C at the prompt just clears the line, unless it's pressed twice in a row and we've
+    // been told to exit when that happens
+    fn handle_interrupt(&mut self) -> Result<()> {
+        if self.exit_on_double_interrupt {
+            if self.interrupted {
+                self.exit.request_exit();
+            } else {
+                writeln!(self.output, "(To exit, press Ctrl-C again or Ctrl-D)")?;
+            }
+        }
+        self.interrupted = !self.interrupted;
+
+        Ok(())
+    }
+
+    fn handle_line(
+        &mut self,
+        editor: &mut rustyline::Editor<Helper<Context>>,
+        eof: &mut bool,
+    ) -> Result<()> {
+        let read = helper::readline(editor, &self.current_prompt(), &mut self.context);
+        if let Some(line) = self.accept_line(editor, read, eof)? {
+            if let Err(error) = self.process_line(line) {
+                self.handle_error(error)?;
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "async")]
+    async fn handle_line_async(
+        &mut self,
+        editor: &mut rustyline::Editor<Helper<Context>>,
+        eof: &mut bool,
+    ) -> Result<()> {
+        let prompt = self.current_prompt();
+        let context = &mut self.context;
+        let read = tokio::task::block_in_place(|| helper::readline(editor, &prompt, context));
+        if let Some(line) = self.accept_line(editor, read, eof)? {
+            if let Err(error) = self.process_line_async(line).await {
+                self.handle_error(error)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Deal with what the line editor read. A line is added to the history and returned, to be
+    // run; the end of input, interrupts and errors are handled here.
+    fn accept_line(
+        &mut self,
+        editor: &mut rustyline::Editor<Helper<Context>>,
+        read: rustyline::Result<String>,
+        eof: &mut bool,
+    ) -> Result<Option<String>> {
+        *eof = false;
+        match read {
+            Ok(line) => {
+                self.interrupted = false;
+                self.history.add(editor, &line)?;
+                Ok(Some(line))
+            }
+            Err(rustyline::error::ReadlineError::Eof) => {
+                *eof = true;
+                Ok(None)
+            }
+            Err(rustyline::error::ReadlineError::Interrupted) => {
+                self.handle_interrupt()?;
+                Ok(None)
+            }
+            Err(error) => {
+                writeln!(self.error_output, "Error reading line: {}", error)?;
+                Ok(None)
+            }
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use crate::error::*;
+    use crate::helper::Helper;
+    use crate::repl::Repl;
+    use crate::script::ScriptFailure;
+    use crate::{initialize_repl, Convert, Value};
+    use crate::{Command, CommandHandler, Parameter, ParameterType, Registry};
+    use clap::{crate_description, crate_name, crate_version};
+    use nix::sys::wait::{waitpid, WaitStatus};
+    use nix::unistd::{close, dup2, fork, pipe, ForkResult};
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    use std::fs::File;
+    use std::io::Write;
+    use std::os::unix::io::FromRawFd;
+    use std::rc::Rc;
+    use std::sync::Arc;
+
+    fn test_error_handler<Context>(
+        error: Error,
+        _repl: &Repl<Context, Error>,
+        _output: &mut dyn Write,
+    ) -> Result<()> {
+        Err(error)
+    }
+
+    fn foo<T>(args: HashMap<String, Value>, _context: &mut T) -> Result<Option<String>> {
+        Ok(Some(format!("foo {:?}", args)))
+    }
+
+    fn run_repl<Context>(mut repl: Repl<Context, Error>, input: &str, expected: Result<()>) {
+        let (rdr, wrtr) = pipe().unwrap();
+        unsafe {
+            match fork() {
                 Ok(ForkResult::Parent { child, .. }) => {
                     // Parent
                     let mut f = File::from_raw_fd(wrtr);
@@ -372,7 +1298,7 @@ mod tests {
 
                     dup2(rdr, 0).unwrap();
                     close(rdr).unwrap();
-                    let mut editor: rustyline::Editor<Helper> = rustyline::Editor::new();
+                    let mut editor: rustyline::Editor<Helper<Context>> = rustyline::Editor::new();
                     let mut eof = false;
                     let result = repl.handle_line(&mut editor, &mut eof);
                     let _ = std::panic::take_hook();
@@ -455,7 +1381,7 @@ mod tests {
         run_repl(
             repl,
             "bar baz\n",
-            Err(Error::UnknownCommand("bar".to_string())),
+            Err(Error::UnknownCommand("bar".to_string(), vec![])),
         );
 
         Ok(())
@@ -518,4 +1444,955 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_closure_as_callback() -> Result<()> {
+        let prefix = String::from("hello");
+        let repl = Repl::new(())
+            .with_name("test")
+            .with_version("v0.1.0")
+            .with_description("Testing 1, 2, 3...")
+            .with_error_handler(test_error_handler)
+            .add_command(
+                Command::new(
+                    "greet",
+                    move |args: HashMap<String, Value>,
+                          _context: &mut ()|
+                          -> Result<Option<String>> {
+                        Ok(Some(format!("{} {}", prefix, args["who"])))
+                    },
+                )
+                .with_parameter(Parameter::new("who").set_required(true)?)?,
+            );
+        run_repl(repl, "greet world\n", Ok(()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_struct_as_callback() -> Result<()> {
+        struct Failing;
+
+        impl CommandHandler<(), Error> for Failing {
+            fn handle(
+                &mut self,
+                _args: HashMap<String, Value>,
+                _context: &mut (),
+            ) -> Result<Option<String>> {
+                Err(Error::UnknownCommand("failing".into(), vec![]))
+            }
+        }
+
+        let repl = Repl::new(())
+            .with_name("test")
+            .with_version("v0.1.0")
+            .with_description("Testing 1, 2, 3...")
+            .with_error_handler(test_error_handler)
+            .add_command(Command::new("fail", Failing));
+        run_repl(
+            repl,
+            "fail\n",
+            Err(Error::UnknownCommand("failing".to_string(), vec![])),
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_argument_of_wrong_type_fails() -> Result<()> {
+        let repl = Repl::new(())
+            .with_name("test")
+            .with_version("v0.1.0")
+            .with_description("Testing 1, 2, 3...")
+            .with_error_handler(test_error_handler)
+            .add_command(
+                Command::new("foo", foo)
+                    .with_parameter(
+                        Parameter::new("count")
+                            .set_required(true)?
+                            .set_type(ParameterType::Int)?,
+                    )?
+                    .with_help("Do foo when you can"),
+            );
+        run_repl(
+            repl,
+            "foo bar\n",
+            Err(Error::InvalidArgument(
+                "count".into(),
+                "int".into(),
+                "bar".into(),
+            )),
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_default_must_match_type() -> Result<()> {
+        assert_eq!(
+            Err(Error::InvalidArgument(
+                "bar".into(),
+                "float".into(),
+                "baz".into()
+            )),
+            Parameter::new("bar")
+                .set_type(ParameterType::Float)?
+                .set_default("baz")
+        );
+        assert_eq!(
+            Err(Error::InvalidArgument(
+                "bar".into(),
+                "address".into(),
+                "baz".into()
+            )),
+            Parameter::new("bar")
+                .set_default("baz")?
+                .set_type(ParameterType::custom::<std::net::IpAddr>("address"))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_named_options_in_any_order() -> Result<()> {
+        let parameters = vec![
+            Parameter::new("file").set_required(true)?,
+            Parameter::new("verbose").set_flag(true)?.set_short('v'),
+            Parameter::new("quiet").set_short('q').set_flag(true)?,
+            Parameter::new("count")
+                .set_long("count")
+                .set_short('n')
+                .set_type(ParameterType::Int)?
+                .set_default("1")?,
+            Parameter::new("tag").set_long("tag").set_multiple(true),
+        ];
+        let validated = Repl::<(), Error>::validate_arguments(
+            "foo",
+            &parameters,
+            &["--tag", "a", "-n", "5", "bar", "--tag=b", "--verbose"],
+        )?;
+        assert_eq!("bar", validated["file"].to_string());
+        let verbose: bool = validated["verbose"].convert()?;
+        let quiet: bool = validated["quiet"].convert()?;
+        let count: i32 = validated["count"].convert()?;
+        assert!(verbose);
+        assert!(!quiet);
+        assert_eq!(5, count);
+        let tags: Vec<String> = validated["tag"].convert()?;
+        assert_eq!(vec!["a", "b"], tags);
+
+        let validated =
+            Repl::<(), Error>::validate_arguments("foo", &parameters, &["-vqn7", "-1"])?;
+        assert_eq!("-1", validated["file"].to_string());
+        let quiet: bool = validated["quiet"].convert()?;
+        let count: i32 = validated["count"].convert()?;
+        assert!(quiet);
+        assert_eq!(7, count);
+        assert!(!validated.contains_key("tag"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bad_options_fail() -> Result<()> {
+        let parameters = vec![
+            Parameter::new("file"),
+            Parameter::new("count")
+                .set_long("count")
+                .set_type(ParameterType::Int)?,
+            Parameter::new("name").set_long("name").set_required(true)?,
+        ];
+        assert_eq!(
+            Err(Error::UnknownOption("foo".into(), "--bogus".into())),
+            Repl::<(), Error>::validate_arguments("foo", &parameters, &["--bogus"]).map(|_| ())
+        );
+        assert_eq!(
+            Err(Error::MissingOptionValue("foo".into(), "--count".into())),
+            Repl::<(), Error>::validate_arguments("foo", &parameters, &["--count"]).map(|_| ())
+        );
+        assert_eq!(
+            Err(Error::InvalidArgument(
+                "count".into(),
+                "int".into(),
+                "x".into()
+            )),
+            Repl::<(), Error>::validate_arguments("foo", &parameters, &["--count", "x"])
+                .map(|_| ())
+        );
+        assert_eq!(
+            Err(Error::MissingRequiredArgument("foo".into(), "name".into())),
+            Repl::<(), Error>::validate_arguments("foo", &parameters, &["--", "--count"])
+                .map(|_| ())
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_variadic_parameter() -> Result<()> {
+        let parameters = vec![
+            Parameter::new("dest").set_required(true)?,
+            Parameter::new("files")
+                .set_variadic(1, Some(3))?
+                .set_type(ParameterType::Path)?,
+        ];
+        let validated =
+            Repl::<(), Error>::validate_arguments("cp", &parameters, &["dir", "a", "b"])?;
+        let files: Vec<std::path::PathBuf> = validated["files"].convert()?;
+        assert_eq!(
+            vec!["a", "b"],
+            files
+                .iter()
+                .map(|f| f.to_str().unwrap())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            Err(Error::MissingRequiredArgument("cp".into(), "files".into())),
+            Repl::<(), Error>::validate_arguments("cp", &parameters, &["dir"]).map(|_| ())
+        );
+        assert_eq!(
+            Err(Error::TooManyArguments("cp".into(), 4)),
+            Repl::<(), Error>::validate_arguments("cp", &parameters, &["d", "a", "b", "c", "e"])
+                .map(|_| ())
+        );
+
+        let parameters = vec![Parameter::new("numbers")
+            .set_variadic(2, None)?
+            .set_type(ParameterType::Int)?];
+        assert_eq!(
+            Err(Error::TooFewArguments("sum".into(), "numbers".into(), 2)),
+            Repl::<(), Error>::validate_arguments("sum", &parameters, &["1"]).map(|_| ())
+        );
+        let validated =
+            Repl::<(), Error>::validate_arguments("sum", &parameters, &["1", "2", "3", "4"])?;
+        let numbers: Vec<i32> = validated["numbers"].convert()?;
+        assert_eq!(vec![1, 2, 3, 4], numbers);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_variadic_must_be_last() -> Result<()> {
+        assert_eq!(
+            Err(Error::IllegalVariadicError("bar".into())),
+            Command::<(), Error>::new("foo", foo)
+                .with_parameter(Parameter::new("baz").set_variadic(0, None)?)?
+                .with_parameter(Parameter::new("bar"))
+        );
+
+        Ok(())
+    }
+
+    fn user_commands() -> Result<Command<(), Error>> {
+        Ok(Command::group("user")
+            .with_subcommand(
+                Command::new("add", foo)
+                    .with_parameter(Parameter::new("name").set_required(true)?)?,
+            )
+            .with_subcommand(Command::new("list", foo))
+            .with_subcommand(Command::group("role").with_subcommand(Command::new("grant", foo))))
+    }
+
+    #[test]
+    fn test_subcommands() -> Result<()> {
+        let repl = Repl::new(())
+            .with_error_handler(test_error_handler)
+            .add_command(user_commands()?);
+        run_repl(repl, "user role grant\n", Ok(()));
+
+        let repl = Repl::new(())
+            .with_error_handler(test_error_handler)
+            .add_command(user_commands()?);
+        run_repl(
+            repl,
+            "user add\n",
+            Err(Error::MissingRequiredArgument(
+                "user add".into(),
+                "name".into(),
+            )),
+        );
+
+        let repl = Repl::new(())
+            .with_error_handler(test_error_handler)
+            .add_command(user_commands()?);
+        run_repl(
+            repl,
+            "user role\n",
+            Err(Error::MissingSubcommand("user role".into())),
+        );
+
+        let repl = Repl::new(())
+            .with_error_handler(test_error_handler)
+            .add_command(user_commands()?);
+        run_repl(
+            repl,
+            "user remove bob\n",
+            Err(Error::UnknownCommand("user remove".into(), vec![])),
+        );
+
+        Ok(())
+    }
+
+    fn quiet_error_handler<Context>(
+        _error: Error,
+        _repl: &Repl<Context, Error>,
+        _output: &mut dyn Write,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    const SCRIPT: &str = "# A comment\n\nfoo a b\nbar\n  # Another comment\nfoo a\nfoo a b\n";
+
+    fn script_repl(output: SharedBuffer) -> Result<Repl<(), Error>> {
+        Ok(Repl::new(())
+            .with_output(output)
+            .with_error_handler(quiet_error_handler)
+            .add_command(
+                Command::new(
+                    "foo",
+                    |args: HashMap<String, Value>, _context: &mut ()| -> Result<Option<String>> {
+                        Ok(Some(format!("foo {} {}", args["bar"], args["baz"])))
+                    },
+                )
+                .with_parameter(Parameter::new("bar").set_required(true)?)?
+                .with_parameter(Parameter::new("baz").set_required(true)?)?,
+            ))
+    }
+
+    #[test]
+    fn test_script_stops_on_error() -> Result<()> {
+        let output = SharedBuffer::default();
+        let mut repl = script_repl(output.clone())?;
+        let summary = repl.run_reader(std::io::Cursor::new(SCRIPT))?;
+        assert_eq!(2, summary.executed);
+        assert_eq!("foo a b\n", output.contents());
+        assert_eq!(
+            vec![ScriptFailure {
+                line_number: 4,
+                line: "bar".into(),
+                message: "Error: Unknown command 'bar'".into(),
+            }],
+            summary.failures
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_script_continues_on_error() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("repl-rs-test-{}.txt", std::process::id()));
+        std::fs::write(&path, SCRIPT)?;
+        let output = SharedBuffer::default();
+        let mut repl = script_repl(output.clone())?.stop_on_error(false);
+        let summary = repl.run_script(&path);
+        std::fs::remove_file(&path)?;
+        let summary = summary?;
+        assert!(!summary.is_success());
+        assert_eq!(4, summary.executed);
+        assert_eq!(
+            vec![4, 6],
+            summary
+                .failures
+                .iter()
+                .map(|failure| failure.line_number)
+                .collect::<Vec<usize>>()
+        );
+
+        assert_eq!("foo a b\nfoo a b\n", output.contents());
+
+        match repl.run_script(&path) {
+            Err(Error::IoError(kind, _)) => assert_eq!(std::io::ErrorKind::NotFound, kind),
+            result => panic!("Unexpected result {:?}", result),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_execute() -> Result<()> {
+        let mut repl = Repl::new(Vec::new())
+            .add_command(
+                Command::new(
+                    "push",
+                    |args: HashMap<String, Value>,
+                     context: &mut Vec<String>|
+                     -> Result<Option<String>> {
+                        context.push(args["item"].to_string());
+                        Ok(None)
+                    },
+                )
+                .with_parameter(Parameter::new("item").set_required(true)?)?,
+            )
+            .add_command(Command::new(
+                "list",
+                |_args: HashMap<String, Value>,
+                 context: &mut Vec<String>|
+                 -> Result<Option<String>> { Ok(Some(context.join(", "))) },
+            ));
+
+        assert_eq!(Ok(None), repl.execute("push \"first item\""));
+        assert_eq!(Ok(None), repl.execute("   "));
+        assert_eq!(Ok(None), repl.execute("push second"));
+        assert_eq!(
+            Ok(Some("first item, second".to_string())),
+            repl.execute("list")
+        );
+        assert_eq!(
+            Err(Error::MissingRequiredArgument("push".into(), "item".into())),
+            repl.execute("push")
+        );
+        assert_eq!(Err(Error::UnterminatedQuote(6)), repl.execute("push 'oops"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unknown_command_suggestions() -> Result<()> {
+        let mut repl = Repl::new(())
+            .add_command(Command::new("list", foo))
+            .add_command(user_commands()?);
+
+        let error = repl.execute("lst").unwrap_err();
+        assert_eq!(
+            Error::UnknownCommand("lst".into(), vec!["list".into()]),
+            error
+        );
+        assert_eq!(
+            "Error: Unknown command 'lst'. Did you mean 'list'?",
+            error.to_string()
+        );
+        assert_eq!(
+            Err(Error::UnknownCommand("hepl".into(), vec!["help".into()])),
+            repl.execute("hepl")
+        );
+        assert_eq!(
+            Err(Error::UnknownCommand(
+                "user lits".into(),
+                vec!["list".into()]
+            )),
+            repl.execute("user lits")
+        );
+        assert_eq!(
+            "Error: Unknown command 'ad'. Did you mean 'add', 'list' or 'role'?",
+            Error::UnknownCommand(
+                "ad".into(),
+                vec!["add".into(), "list".into(), "role".into()]
+            )
+            .to_string()
+        );
+
+        Ok(())
+    }
+
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl SharedBuffer {
+        fn contents(&self) -> String {
+            String::from_utf8(self.0.borrow().clone()).unwrap()
+        }
+    }
+
+    #[test]
+    fn test_output_sinks() -> Result<()> {
+        let output = SharedBuffer::default();
+        let error_output = SharedBuffer::default();
+        let mut repl = Repl::new(())
+            .with_name("test")
+            .with_version("v0.1.0")
+            .with_description("Testing 1, 2, 3...")
+            .with_output(output.clone())
+            .with_error_output(error_output.clone())
+            .stop_on_error(false)
+            .add_command(
+                Command::new("foo", foo)
+                    .with_parameter(Parameter::new("bar").set_required(true)?)?
+                    .with_help("Do foo when you can"),
+            );
+        repl.run_reader(std::io::Cursor::new(
+            "help\nhelp foo\nhelp baz\nbaz\nfoo x\n",
+        ))?;
+
+        let output = output.contents();
+        assert!(output.starts_with("test v0.1.0: Testing 1, 2, 3...\n"));
+        assert!(output.contains("foo - Do foo when you can\n"));
+        assert!(output.contains("foo: Do foo when you can\nUsage:\n\tfoo bar\n"));
+        assert!(output.contains("foo {\"bar\": "));
+        assert_eq!(
+            "Error: Unknown command 'baz'\nError: Unknown command 'baz'\n",
+            error_output.contents()
+        );
+        assert_eq!(
+            Err(Error::UnknownCommand("fo".into(), vec!["foo".into()])),
+            repl.execute("help fo")
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_exit() -> Result<()> {
+        let mut repl = Repl::new(vec![])
+            .with_error_handler(test_error_handler)
+            .add_command(
+                Command::new(
+                    "push",
+                    |args: HashMap<String, Value>,
+                     context: &mut Vec<String>|
+                     -> Result<Option<String>> {
+                        context.push(args["item"].to_string());
+                        Ok(None)
+                    },
+                )
+                .with_parameter(Parameter::new("item").set_required(true)?)?,
+            )
+            .on_exit(|context: &mut Vec<String>| {
+                context.push("flushed".into());
+                Ok(())
+            });
+        let exit = repl.exit_handle();
+        repl = repl.add_command(Command::new(
+            "logout",
+            move |_args: HashMap<String, Value>,
+                  _context: &mut Vec<String>|
+                  -> Result<Option<String>> {
+                exit.request_exit();
+                Ok(None)
+            },
+        ));
+
+        let summary = repl.run_reader(std::io::Cursor::new("push a\nquit\npush b\n"))?;
+        assert_eq!(2, summary.executed);
+        assert_eq!(vec!["a", "flushed"], *repl.context.as_ref().unwrap());
+        assert!(!repl.is_exit_requested());
+
+        repl.run_reader(std::io::Cursor::new("push c\nlogout\npush d\n"))?;
+        assert_eq!(
+            vec!["a", "flushed", "c", "flushed"],
+            *repl.context.as_ref().unwrap()
+        );
+
+        // A script which doesn't ask to exit doesn't call the hooks
+        repl.run_reader(std::io::Cursor::new("push d\n"))?;
+        assert_eq!(
+            Err(Error::UnknownCommand("pop".into(), vec![])),
+            repl.run_reader(std::io::Cursor::new("pop\npush e\n"))
+        );
+        assert_eq!(
+            vec!["a", "flushed", "c", "flushed", "d"],
+            *repl.context.as_ref().unwrap()
+        );
+
+        // A session calls them as it ends, whether or not it's asked to exit
+        repl.run_session(
+            std::io::Cursor::new("push e\n"),
+            std::io::sink(),
+            std::io::sink(),
+        )?;
+        repl.run_session(
+            std::io::Cursor::new("exit\npush f\n"),
+            std::io::sink(),
+            std::io::sink(),
+        )?;
+        assert_eq!(
+            vec!["a", "flushed", "c", "flushed", "d", "e", "flushed", "flushed"],
+            *repl.context.as_ref().unwrap()
+        );
+
+        // As does execute when the line asks to exit, once until the Repl is run again
+        repl.context.as_mut().unwrap().clear();
+        assert_eq!(Ok(None), repl.execute("push g"));
+        assert_eq!(Ok(None), repl.execute("exit"));
+        assert!(repl.is_exit_requested());
+        assert_eq!(Ok(None), repl.execute("quit"));
+        assert_eq!(vec!["g", "flushed"], *repl.context.as_ref().unwrap());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_exit_can_be_overridden() -> Result<()> {
+        let mut repl = Repl::new(()).add_command(Command::new("exit", foo));
+        assert_eq!(Ok(Some("foo {}".to_string())), repl.execute("exit"));
+        assert!(!repl.is_exit_requested());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_interrupt_at_prompt() -> Result<()> {
+        let mut repl = Repl::<(), Error>::new(()).with_output(SharedBuffer::default());
+        repl.handle_interrupt()?;
+        repl.handle_interrupt()?;
+        assert!(!repl.is_exit_requested());
+
+        let output = SharedBuffer::default();
+        let mut repl = Repl::<(), Error>::new(())
+            .with_output(output.clone())
+            .exit_on_double_interrupt(true);
+        repl.handle_interrupt()?;
+        assert!(!repl.is_exit_requested());
+        assert_eq!(
+            "(To exit, press Ctrl-C again or Ctrl-D)\n",
+            output.contents()
+        );
+        repl.handle_interrupt()?;
+        assert!(repl.is_exit_requested());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_cancellation_token() -> Result<()> {
+        let repl = Repl::<(), Error>::new(());
+        let token = repl.cancellation_token();
+        let mut repl = repl.add_command(Command::new(
+            "work",
+            move |_args: HashMap<String, Value>, _context: &mut ()| -> Result<Option<String>> {
+                let mut steps = 0;
+                while !token.is_cancelled() {
+                    steps += 1;
+                    if steps == 3 {
+                        // Stand-in for Ctrl-C arriving while the command runs
+                        token.cancel();
+                    }
+                }
+                Ok(Some(steps.to_string()))
+            },
+        ));
+        let token = repl.cancellation_token();
+        token.cancel();
+        assert_eq!(Ok(Some("3".to_string())), repl.execute("work"));
+        assert!(token.is_cancelled());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_prompt_fn() -> Result<()> {
+        let mut repl = Repl::new(0)
+            .with_prompt_fn(|count: &i32| format!("[{}]> ", count))
+            .with_name("test")
+            .add_command(Command::new(
+                "inc",
+                |_args: HashMap<String, Value>, count: &mut i32| -> Result<Option<String>> {
+                    *count += 1;
+                    Ok(None)
+                },
+            ));
+        assert_eq!("[0]> ", repl.current_prompt());
+        repl.execute("inc")?;
+        assert_eq!("[1]> ", repl.current_prompt());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_aliases() -> Result<()> {
+        let mut repl = Repl::new(())
+            .add_command(
+                Command::new(
+                    "list",
+                    |args: HashMap<String, Value>, _context: &mut ()| -> Result<Option<String>> {
+                        Ok(Some(format!("{} {}", args["long"], args["dir"])))
+                    },
+                )
+                .with_alias("ls")
+                .with_parameter(Parameter::new("long").set_flag(true)?)?
+                .with_parameter(Parameter::new("dir").set_default(".")?)?
+                .with_help("List files"),
+            )
+            .add_command(
+                Command::group("user").with_subcommand(Command::new("list", foo).with_alias("ls")),
+            );
+
+        assert_eq!(Ok(Some("false /tmp".to_string())), repl.execute("ls /tmp"));
+        assert_eq!(Ok(Some("foo {}".to_string())), repl.execute("user ls"));
+
+        assert_eq!(Ok(None), repl.execute("alias ll ls --long"));
+        assert_eq!(Ok(None), repl.execute("alias lt ll 'my dir'"));
+        assert_eq!(Ok(Some("true .".to_string())), repl.execute("ll"));
+        assert_eq!(Ok(Some("true my dir".to_string())), repl.execute("lt"));
+        assert_eq!(Ok(Some("true /tmp".to_string())), repl.execute("ll /tmp"));
+        assert_eq!(
+            Ok(Some("ll = ls --long\nlt = ls --long 'my dir'".to_string())),
+            repl.execute("alias")
+        );
+
+        assert_eq!(
+            Err(Error::IllegalAliasError("ls".into())),
+            repl.execute("alias ls user list")
+        );
+        assert_eq!(
+            Err(Error::UnknownCommand("lisst".into(), vec!["list".into()])),
+            repl.execute("alias l lisst")
+        );
+        assert_eq!(
+            Err(Error::MissingRequiredArgument(
+                "alias".into(),
+                "command".into()
+            )),
+            repl.execute("alias l")
+        );
+
+        let help = repl.execute("help")?.unwrap();
+        assert!(help.contains("list (ls) - List files\n"));
+        assert!(help.contains("Aliases:\n\tll = ls --long\n"));
+        let help = repl.execute("help ll")?.unwrap();
+        assert!(help.starts_with("list: List files\nAliases: ls\nUsage:\n"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_multi_line_input() -> Result<()> {
+        let mut repl = Repl::new(Vec::new())
+            .add_command(
+                Command::new(
+                    "write",
+                    |args: HashMap<String, Value>,
+                     context: &mut Vec<String>|
+                     -> Result<Option<String>> {
+                        let text = args
+                            .get("text")
+                            .map_or("none".into(), |text| text.to_string());
+                        context.push(format!("{}: {}", args["file"], text));
+                        Ok(None)
+                    },
+                )
+                .with_parameter(Parameter::new("file").set_required(true)?)?
+                .with_body("text"),
+            )
+            .add_command(
+                Command::new(
+                    "push",
+                    |args: HashMap<String, Value>,
+                     context: &mut Vec<String>|
+                     -> Result<Option<String>> {
+                        context.push(args["items"].to_string());
+                        Ok(None)
+                    },
+                )
+                .with_parameter(Parameter::new("items").set_variadic(1, None)?)?,
+            );
+
+        repl.execute("write a.txt <<END\nfirst\n  second\nEND")?;
+        repl.execute("write b.txt")?;
+        assert_eq!(
+            Err(Error::UnterminatedBody("END".into())),
+            repl.execute("write c.txt <<END\nfirst")
+        );
+        let summary = repl.run_reader(std::io::Cursor::new(
+            "push one \\\n  two\nwrite 'd \n.txt' <<EOF\n# not a comment\n\nEOF\npush three\n",
+        ))?;
+        assert!(summary.is_success());
+        assert_eq!(3, summary.executed);
+        assert_eq!(
+            vec![
+                "a.txt: first\n  second\n",
+                "b.txt: none",
+                "one two",
+                "d \n.txt: # not a comment\n\n",
+                "three"
+            ],
+            *repl.context.as_ref().unwrap()
+        );
+
+        Ok(())
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn test_async_commands() -> Result<()> {
+        let mut repl = Repl::new(0)
+            .add_command(
+                Command::new_async("add", |args: HashMap<String, Value>, total: &mut i64| {
+                    Box::pin(async move {
+                        tokio::task::yield_now().await;
+                        let amount: i64 = args["amount"].convert()?;
+                        *total += amount;
+                        Ok(Some(total.to_string()))
+                    })
+                })
+                .with_parameter(
+                    Parameter::new("amount")
+                        .set_type(ParameterType::Int)?
+                        .set_required(true)?,
+                )?,
+            )
+            .add_command(Command::new("total", |_args, total: &mut i64| {
+                Ok(Some(total.to_string()))
+            }));
+
+        assert_eq!(Ok(Some("2".into())), repl.execute_async("add 2").await);
+        assert_eq!(Ok(Some("5".into())), repl.execute_async("add 3").await);
+        assert_eq!(Ok(Some("5".into())), repl.execute_async("total").await);
+        assert_eq!(
+            Err(Error::MissingRequiredArgument(
+                "add".into(),
+                "amount".into()
+            )),
+            repl.execute_async("add").await
+        );
+        assert_eq!(
+            Err(Error::AsyncCommand("add".into())),
+            repl.execute("add 1")
+        );
+
+        Ok(())
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn test_run_async_needs_multi_threaded_runtime() {
+        let mut repl = Repl::<(), Error>::new(());
+        assert_eq!(Err(Error::CurrentThreadRuntime), repl.run_async().await);
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn test_cancel_async_command() -> Result<()> {
+        let mut repl = Repl::new(()).add_command(Command::new_async(
+            "hang",
+            |_args: HashMap<String, Value>, _context: &mut ()| {
+                Box::pin(async {
+                    std::future::pending::<()>().await;
+                    Ok(None)
+                })
+            },
+        ));
+        let token = repl.cancellation_token();
+        std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(50));
+            token.cancel();
+        });
+
+        assert_eq!(Err(Error::Cancelled), repl.execute_async("hang").await);
+
+        Ok(())
+    }
+
+    // Send the input to the session served over the connection, and return everything written
+    // back until the session ends
+    fn serve_session<S: std::io::Read + Write>(mut client: S, input: &str) -> String {
+        client.write_all(input.as_bytes()).unwrap();
+        let mut transcript = String::new();
+        client.read_to_string(&mut transcript).unwrap();
+        transcript
+    }
+
+    fn server_repl() -> Repl<Vec<String>, Error> {
+        Repl::new(vec![])
+            .with_name("server")
+            .with_version("1.0")
+            .with_prompt(&"> ")
+            .add_command(push_command())
+    }
+
+    fn push_command() -> Command<Vec<String>, Error> {
+        Command::new(
+            "push",
+            |args: HashMap<String, Value>, context: &mut Vec<String>| -> Result<Option<String>> {
+                context.push(args["item"].to_string());
+                Ok(Some(context.join(" ")))
+            },
+        )
+        .with_parameter(Parameter::new("item").set_required(true).unwrap())
+        .unwrap()
+    }
+
+    #[test]
+    fn test_registry_serve_tcp() {
+        let registry = Arc::new(
+            Registry::new()
+                .with_name("server")
+                .with_version("1.0")
+                .add_command(|_, _| push_command()),
+        );
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            registry.serve_tcp(listener, |registry| {
+                Repl::with_registry(registry, vec![]).with_prompt(&"> ")
+            })
+        });
+
+        // Both sessions are open at once, and each has its own context
+        let first = std::net::TcpStream::connect(address).unwrap();
+        let second = std::net::TcpStream::connect(address).unwrap();
+        assert_eq!(
+            "Welcome to server 1.0\n> b\n> ",
+            serve_session(second, "push b\nexit\n")
+        );
+        assert_eq!(
+            "Welcome to server 1.0\n> a\n> a c\n> ",
+            serve_session(first, "push a\npush c\nexit\n")
+        );
+    }
+
+    #[test]
+    fn test_serve_tcp() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        std::thread::spawn(move || server_repl().serve_tcp(listener));
+
+        let client = std::net::TcpStream::connect(address).unwrap();
+        assert_eq!(
+            "Welcome to server 1.0\n> a\n> Error: Unknown command 'psh'. Did you mean 'push'?\n\
+             > a b\nc\n> ",
+            serve_session(client, "push a\npsh b\npush 'b\nc'\nexit\n")
+        );
+        // The next connection gets a new session, with the same context
+        let client = std::net::TcpStream::connect(address).unwrap();
+        assert_eq!(
+            "Welcome to server 1.0\n> a b\nc d\n> ",
+            serve_session(client, "push d\nquit\n")
+        );
+    }
+
+    #[test]
+    fn test_session_timeout() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            server_repl()
+                .with_session_timeout(std::time::Duration::from_millis(100))
+                .with_error_output(std::io::sink())
+                .serve_tcp(listener)
+        });
+
+        // An idle client is dropped, rather than keeping the next one waiting
+        let idle = std::net::TcpStream::connect(address).unwrap();
+        let client = std::net::TcpStream::connect(address).unwrap();
+        assert_eq!(
+            "Welcome to server 1.0\n> a\n> ",
+            serve_session(client, "push a\nexit\n")
+        );
+        assert_eq!("Welcome to server 1.0\n> ", serve_session(idle, ""));
+    }
+
+    #[test]
+    fn test_serve_unix() {
+        let path = std::env::temp_dir().join(format!("repl-rs-test-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = std::os::unix::net::UnixListener::bind(&path).unwrap();
+        std::thread::spawn(move || server_repl().serve_unix(listener));
+
+        let client = std::os::unix::net::UnixStream::connect(&path).unwrap();
+        assert_eq!(
+            "Welcome to server 1.0\n> a\n> ",
+            serve_session(client, "push a\nexit\n")
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
 }
@@ -0,0 +1,30 @@
+/// A line of a script which failed, as reported in a [ScriptSummary](struct.ScriptSummary.html)
+#[derive(Debug, PartialEq)]
+pub struct ScriptFailure {
+    /// Line number in the script, starting at 1
+    pub line_number: usize,
+
+    /// The line itself
+    pub line: String,
+
+    /// The error message
+    pub message: String,
+}
+
+/// Summary of running a script with [Repl::run_script](struct.Repl.html#method.run_script) or
+/// [Repl::run_reader](struct.Repl.html#method.run_reader)
+#[derive(Debug, Default, PartialEq)]
+pub struct ScriptSummary {
+    /// Number of commands executed, not counting blank lines and comments
+    pub executed: usize,
+
+    /// Lines which failed
+    pub failures: Vec<ScriptFailure>,
+}
+
+impl ScriptSummary {
+    /// Whether every line in the script succeeded
+    pub fn is_success(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
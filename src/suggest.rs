@@ -0,0 +1,71 @@
+// Most suggestions given for a mistyped command
+const MAX_SUGGESTIONS: usize = 3;
+
+// Find the candidates close enough to what was typed that it was probably a typo for them,
+// closest first. How many edits are allowed grows with the length of what was typed, so short
+// words don't match everything.
+pub(crate) fn suggestions<'a>(
+    typed: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> Vec<String> {
+    let max_distance = typed.chars().count().div_ceil(3).max(1);
+    let mut close = candidates
+        .into_iter()
+        .map(|candidate| (edit_distance(typed, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .collect::<Vec<(usize, &str)>>();
+    close.sort();
+    close.dedup();
+
+    close
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, candidate)| candidate.to_string())
+        .collect()
+}
+
+// Number of single-character insertions, deletions, substitutions or swaps of adjacent
+// characters needed to turn one string into the other
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a = a.chars().collect::<Vec<char>>();
+    let b = b.chars().collect::<Vec<char>>();
+    let mut rows = vec![(0..=b.len()).collect::<Vec<usize>>()];
+    for i in 1..=a.len() {
+        let mut row = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            row[j] = (rows[i - 1][j] + 1)
+                .min(row[j - 1] + 1)
+                .min(rows[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                row[j] = row[j].min(rows[i - 2][j - 2] + 1);
+            }
+        }
+        rows.push(row);
+    }
+
+    rows[a.len()][b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::suggest::{edit_distance, suggestions};
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(0, edit_distance("list", "list"));
+        assert_eq!(1, edit_distance("lst", "list"));
+        assert_eq!(1, edit_distance("hlep", "help"));
+        assert_eq!(3, edit_distance("", "foo"));
+        assert_eq!(3, edit_distance("kitten", "sitting"));
+    }
+
+    #[test]
+    fn test_suggestions() {
+        let commands = ["list", "last", "load", "help", "exit"];
+        assert_eq!(vec!["last", "list"], suggestions("lst", commands));
+        assert_eq!(vec!["help"], suggestions("hepl", commands));
+        assert!(suggestions("frobnicate", commands).is_empty());
+        assert!(suggestions("x", commands).is_empty());
+    }
+}
@@ -1,15 +1,26 @@
 use crate::Result;
 use std::fmt;
+use std::path::PathBuf;
 
-/// Value type. Has conversions to every primitive type.
+#[derive(Clone, Debug)]
+enum Inner {
+    Single(String),
+    List(Vec<String>),
+}
+
+/// Value type. Has conversions to every primitive type, and to a `Vec` of them for arguments
+/// which can be given more than once.
 #[derive(Clone, Debug)]
 pub struct Value {
-    value: String,
+    value: Inner,
 }
 
 impl fmt::Display for Value {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.value)
+        match &self.value {
+            Inner::Single(value) => write!(f, "{}", value),
+            Inner::List(values) => write!(f, "{}", values.join(" ")),
+        }
     }
 }
 
@@ -21,14 +32,41 @@ pub trait Convert<T> {
 impl Value {
     pub(crate) fn new(value: &str) -> Self {
         Self {
-            value: value.to_string(),
+            value: Inner::Single(value.to_string()),
+        }
+    }
+
+    pub(crate) fn new_list(values: Vec<String>) -> Self {
+        Self {
+            value: Inner::List(values),
         }
     }
 }
 
 impl Convert<String> for Value {
     fn convert(&self) -> Result<String> {
-        Ok(self.value.to_string())
+        Ok(self.to_string())
+    }
+}
+
+impl Convert<PathBuf> for Value {
+    fn convert(&self) -> Result<PathBuf> {
+        Ok(PathBuf::from(self.to_string()))
+    }
+}
+
+impl<T> Convert<Vec<T>> for Value
+where
+    Value: Convert<T>,
+{
+    fn convert(&self) -> Result<Vec<T>> {
+        match &self.value {
+            Inner::Single(value) => Ok(vec![Value::new(value).convert()?]),
+            Inner::List(values) => values
+                .iter()
+                .map(|value| Value::new(value).convert())
+                .collect(),
+        }
     }
 }
 
@@ -36,7 +74,7 @@ macro_rules! add_num_converter {
     ($type: ident) => {
         impl Convert<$type> for Value {
             fn convert(&self) -> Result<$type> {
-                Ok(self.value.parse::<$type>()?)
+                Ok(self.to_string().parse::<$type>()?)
             }
         }
     };